@@ -1,4 +1,5 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::compile_protos("proto/service.proto")?;
+    tonic_build::compile_protos("proto/journal.proto")?;
     Ok(())
 }
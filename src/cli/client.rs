@@ -1,5 +1,7 @@
 use crate::cipher::Cipher;
 use clap::arg_enum;
+use nix::sys::signal::Signal;
+use std::str::FromStr;
 use structopt::StructOpt;
 use uuid::Uuid;
 
@@ -8,33 +10,231 @@ arg_enum! {
     pub enum Descriptor {
         Stdout,
         Stderr,
+        Both,
     }
 }
 
+arg_enum! {
+    #[derive(StructOpt, Debug)]
+    pub enum LogPolicy {
+        Truncate,
+        Rotate,
+        Kill,
+    }
+}
+
+/// Disk IO limits of a single device, given as the device path or its
+/// major:minor numbers followed by any of rbps, wbps, riops and wiops,
+/// e.g. /dev/sda,rbps=1048576,wiops=100
+#[derive(Debug, Default)]
+pub struct DiskRule {
+    pub device: String,
+    pub read_bps: u64,
+    pub write_bps: u64,
+    pub read_iops: u64,
+    pub write_iops: u64,
+}
+
+impl FromStr for DiskRule {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let mut parts = rule.split(',');
+
+        let mut disk_rule = DiskRule {
+            device: parts.next().unwrap_or_default().to_string(),
+            ..Default::default()
+        };
+
+        for part in parts {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, got {}", part))?;
+
+            let value = value
+                .parse()
+                .map_err(|_| format!("invalid value of {}: {}", key, value))?;
+
+            match key {
+                "rbps" => disk_rule.read_bps = value,
+                "wbps" => disk_rule.write_bps = value,
+                "riops" => disk_rule.read_iops = value,
+                "wiops" => disk_rule.write_iops = value,
+                _ => return Err(format!("unknown limit: {}", key)),
+            }
+        }
+
+        Ok(disk_rule)
+    }
+}
+
+/// The arguments of `run`, boxed in `Command` as they outweigh the others by far
+#[derive(StructOpt, Debug)]
+pub struct RunArgs {
+    #[structopt(long)]
+    /// Max memory share
+    pub memory: Option<u64>,
+
+    #[structopt(long)]
+    /// Max cpu share
+    pub cpu: Option<u64>,
+
+    #[structopt(long)]
+    /// Max number of CPUs worth of CPU time, e.g. 1.5
+    pub cpus: Option<f64>,
+
+    #[structopt(long)]
+    /// CPUs to run on, e.g. 0-3,6
+    pub cpuset: Option<String>,
+
+    #[structopt(long)]
+    /// Memory (NUMA) nodes to use, e.g. 0-1
+    pub cpuset_mems: Option<String>,
+
+    #[structopt(long)]
+    /// Max read and write bytes/s for all disk devices
+    pub disk: Option<u64>,
+
+    #[structopt(long = "disk-rule", number_of_values = 1)]
+    /// Disk IO limits of a single device overriding --disk, e.g.
+    /// /dev/sda,rbps=1048576,wbps=1048576,riops=100,wiops=100 (can be repeated)
+    pub disk_rules: Vec<DiskRule>,
+
+    #[structopt(long)]
+    /// Max number of processes and threads the command can have at once
+    pub pids: Option<u32>,
+
+    #[structopt(long, number_of_values = 1, parse(try_from_str = parse_env))]
+    /// Environment variable to set, e.g. RUST_LOG=info (can be repeated)
+    pub env: Vec<(String, String)>,
+
+    #[structopt(long = "env-file")]
+    /// File with the environment variables to set, one KEY=VALUE per line
+    /// (overridden by --env)
+    pub env_file: Option<String>,
+
+    #[structopt(long = "clear-env")]
+    /// Don't pass the environment of the server to the command
+    pub clear_env: bool,
+
+    #[structopt(long)]
+    /// Directory to run the command in
+    pub cwd: Option<String>,
+
+    #[structopt(long = "stdin-file")]
+    /// File to feed into the command's stdin, - for the client's stdin
+    pub stdin_file: Option<String>,
+
+    #[structopt(long = "open-stdin")]
+    /// Keep the command's stdin open for `attach`
+    pub open_stdin: bool,
+
+    #[structopt(long, short)]
+    /// Run the command in a pseudo-terminal of the size of the local one,
+    /// for interactive programs such as shells
+    pub tty: bool,
+
+    #[structopt(long)]
+    /// Seconds the command can run for before it gets stopped as with `stop`
+    pub timeout: Option<u64>,
+
+    #[structopt(long)]
+    /// User to run the command as, by its name or UID
+    pub user: Option<String>,
+
+    #[structopt(long)]
+    /// Group to run the command in, by its name or GID (the user's primary
+    /// group by default)
+    pub group: Option<String>,
+
+    #[structopt(long = "supplementary-group", number_of_values = 1)]
+    /// Supplementary group of the command, by its name or GID (all of the
+    /// user's groups by default, can be repeated)
+    pub supplementary_groups: Vec<String>,
+
+    #[structopt(long)]
+    /// Max bytes of the output kept on disk
+    pub max_log_size: Option<u64>,
+
+    #[structopt(long, default_value = "truncate")]
+    /// What to do when the output goes over its max size (truncate | rotate | kill)
+    pub log_policy: LogPolicy,
+
+    #[structopt(long)]
+    /// The number of segments to rotate the output into
+    pub log_segments: Option<u32>,
+
+    #[structopt(long, alias = "attach")]
+    /// Stream the command's output and exit with its exit code
+    pub follow: bool,
+
+    /// Command to run
+    pub command: String,
+
+    /// Commands arguments
+    pub args: Vec<String>,
+}
+
 #[derive(StructOpt, Debug)]
 pub enum Command {
     /// Run a command
-    Run {
-        #[structopt(long)]
-        /// Max memory share
-        memory: Option<u64>,
+    Run(Box<RunArgs>),
+    /// Stop a command
+    Stop {
+        /// Task ID as returned from `run`
+        id: Uuid,
 
-        #[structopt(long)]
-        /// Max cpu share
-        cpu: Option<u64>,
+        #[structopt(long, parse(try_from_str = parse_signal))]
+        /// Signal to stop the command with, by its name or number (TERM by default)
+        signal: Option<i32>,
+
+        #[structopt(long = "grace-period")]
+        /// Seconds to wait for the command to exit before killing it (5 by default)
+        grace_period: Option<u32>,
+    },
+    /// Send a signal to a command
+    Signal {
+        /// Task ID as returned from `run`
+        id: Uuid,
+
+        #[structopt(parse(try_from_str = parse_signal))]
+        /// Signal by its name or number, e.g. HUP, SIGUSR1 or 10
+        signal: i32,
 
         #[structopt(long)]
-        /// Max read and write bytes/s for all disk devices
-        disk: Option<u64>,
+        /// Send the signal to all of the command's processes, not just the main one
+        group: bool,
+    },
+    /// Pause a running command by freezing it
+    Pause {
+        /// Task ID as returned from `run`
+        id: Uuid,
+    },
+    /// Resume a paused command
+    Resume {
+        /// Task ID as returned from `run`
+        id: Uuid,
+    },
+    /// Attach to a command, forwarding the stdin into it and streaming its output
+    Attach {
+        /// Task ID as returned from `run`
+        id: Uuid,
 
-        /// Command to run
-        command: String,
+        /// Start with the given number of the last lines of the output
+        #[structopt(long)]
+        tail: Option<u32>,
 
-        /// Commands arguments
-        args: Vec<String>,
+        /// Keys detaching from a command running in a pseudo-terminal, e.g.
+        /// ctrl-a,d
+        #[structopt(
+            long = "detach-keys",
+            default_value = "ctrl-p,ctrl-q",
+            parse(try_from_str = parse_detach_keys)
+        )]
+        detach_keys: DetachKeys,
     },
-    /// Stop a command
-    Stop {
+    /// Delete a finished command along with its logs
+    Delete {
         /// Task ID as returned from `run`
         id: Uuid,
     },
@@ -43,16 +243,138 @@ pub enum Command {
         /// Task ID as returned from `run`
         id: Uuid,
     },
+    /// Show command's resource usage
+    Stats {
+        /// Task ID as returned from `run`
+        id: Uuid,
+
+        #[structopt(long)]
+        /// Keep showing the usage until the command finishes
+        watch: bool,
+
+        #[structopt(long, default_value = "1000")]
+        /// How often to show the usage with --watch, in milliseconds
+        interval: u32,
+    },
+    /// Wait for a command to finish and exit with its exit code
+    Wait {
+        /// Task ID as returned from `run`
+        id: Uuid,
+
+        #[structopt(long)]
+        /// Give up after given number of seconds
+        timeout: Option<u64>,
+    },
+    /// List commands
+    List {
+        #[structopt(long, conflicts_with = "stopped")]
+        /// Only list running commands
+        running: bool,
+
+        #[structopt(long)]
+        /// Only list stopped commands
+        stopped: bool,
+
+        #[structopt(long, conflicts_with_all = &["running", "stopped"])]
+        /// Only list paused commands
+        paused: bool,
+
+        #[structopt(long)]
+        /// Only list commands starting with given prefix
+        command: Option<String>,
+
+        #[structopt(long = "started-after")]
+        /// Only list commands started after given UNIX timestamp (in seconds)
+        started_after: Option<u64>,
+
+        #[structopt(long = "page-size")]
+        /// Max number of commands to list
+        page_size: Option<u32>,
+
+        #[structopt(long = "page-token")]
+        /// Token to list the next page of commands with
+        page_token: Option<String>,
+    },
+    /// Watch commands' lifecycle events
+    Watch {
+        /// Task IDs as returned from `run` - all tasks are watched when none are given
+        ids: Vec<Uuid>,
+    },
     /// Follow command's logs
     Log {
         /// Task ID as returned from `run`
         id: Uuid,
 
-        /// Process output descriptor (stdout | stderr)
+        /// Process output descriptor (stdout | stderr | both)
         descriptor: Descriptor,
+
+        /// Start with the given number of the last lines of the output
+        #[structopt(long, conflicts_with = "offset")]
+        tail: Option<u32>,
+
+        /// Start at the given byte offset of the output (not with both)
+        #[structopt(long)]
+        offset: Option<u64>,
+
+        /// Print the output captured so far and exit
+        #[structopt(long)]
+        no_follow: bool,
     },
 }
 
+/// Parses a signal given by its number or by its name, with or without
+/// the SIG prefix, e.g. 1, HUP or SIGHUP
+pub fn parse_signal(signal: &str) -> Result<i32, String> {
+    if let Ok(number) = signal.parse() {
+        return Ok(number);
+    }
+
+    let name = signal.to_uppercase();
+    let name = if name.starts_with("SIG") {
+        name
+    } else {
+        format!("SIG{}", name)
+    };
+
+    name.parse::<Signal>()
+        .map(|signal| signal as i32)
+        .map_err(|_| format!("unknown signal: {}", signal))
+}
+
+/// Parses an environment variable given as KEY=VALUE
+pub fn parse_env(variable: &str) -> Result<(String, String), String> {
+    match variable.split_once('=') {
+        Some((key, value)) => Ok((key.to_string(), value.to_string())),
+        None => Err(format!("expected KEY=VALUE, got {}", variable)),
+    }
+}
+
+/// The sequence of bytes a client types to detach from a command
+#[derive(Debug)]
+pub struct DetachKeys(pub Vec<u8>);
+
+/// Parses the keys detaching from a command, given as a comma separated list
+/// of single characters and ctrl-<character> combinations, e.g. ctrl-p,ctrl-q
+pub fn parse_detach_keys(keys: &str) -> Result<DetachKeys, String> {
+    keys.split(',')
+        .map(|key| {
+            let (control, character) = match key.strip_prefix("ctrl-") {
+                Some(character) => (true, character),
+                None => (false, key),
+            };
+
+            match character.as_bytes() {
+                [byte] if control && matches!(byte, b'a'..=b'z' | b'@' | b'['..=b'_') => {
+                    Ok(byte & 0x1f)
+                }
+                [byte] if !control && byte.is_ascii() => Ok(*byte),
+                _ => Err(format!("invalid detach key: {}", key)),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(DetachKeys)
+}
+
 #[derive(StructOpt, Debug)]
 pub struct Cli {
     /// Path to the server's CA root certificate
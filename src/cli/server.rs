@@ -30,4 +30,81 @@ pub struct Cli {
         default_value = "[::1]:50051"
     )]
     pub address: String,
+
+    /// Directory to keep the processes logs in
+    #[structopt(long = "log-dir", env = "LOG_DIR", default_value = "tmp")]
+    pub log_dir: String,
+
+    /// Path to the journal persisting the processes between restarts
+    #[structopt(long = "journal", env = "JOURNAL", default_value = "tmp/jobs.journal")]
+    pub journal: String,
+
+    /// The maximum number of bytes of the output of a single process kept on disk
+    /// unless requested otherwise
+    #[structopt(long = "max-log-size", env = "MAX_LOG_SIZE")]
+    pub max_log_size: Option<u64>,
+
+    /// The maximum number of bytes of the output of all of the processes kept on disk
+    #[structopt(long = "max-logs-size", env = "MAX_LOGS_SIZE")]
+    pub max_logs_size: Option<u64>,
+
+    /// How long to keep the finished processes and their logs, in seconds
+    #[structopt(long = "retain-for", env = "RETAIN_FOR")]
+    pub retain_for: Option<u64>,
+
+    /// The maximum number of the finished processes to keep along with their logs
+    #[structopt(long = "retain-jobs", env = "RETAIN_JOBS")]
+    pub retain_jobs: Option<usize>,
+
+    /// The number of bytes of the logs of all of the processes over which the oldest
+    /// finished processes get deleted
+    #[structopt(long = "retain-logs-size", env = "RETAIN_LOGS_SIZE")]
+    pub retain_logs_size: Option<u64>,
+
+    /// How often to delete the finished processes going over the retention limits,
+    /// in seconds
+    #[structopt(
+        long = "retention-interval",
+        env = "RETENTION_INTERVAL",
+        default_value = "60",
+        parse(try_from_str = parse_interval)
+    )]
+    pub retention_interval: u64,
+
+    /// Users the clients can run the commands as, given as the common name of the
+    /// client's certificate followed by the names of the users, e.g. client=alice,bob
+    /// or client=* for any user (can be repeated, adding up for the same client)
+    #[structopt(
+        long = "allow-users",
+        number_of_values = 1,
+        parse(try_from_str = parse_allowed_users)
+    )]
+    pub allow_users: Vec<(String, Vec<String>)>,
+
+    /// User the commands run as when the client doesn't name one, rather than the
+    /// user of the server (allowed for all of the clients)
+    #[structopt(long = "default-user", env = "DEFAULT_USER")]
+    pub default_user: Option<String>,
+}
+
+/// Parses the number of seconds between the runs of a periodic task, which
+/// can't be 0
+pub fn parse_interval(seconds: &str) -> Result<u64, String> {
+    match seconds.parse() {
+        Ok(0) => Err("the interval has to be at least 1 second".to_string()),
+        Ok(seconds) => Ok(seconds),
+        Err(err) => Err(format!("invalid interval {}: {}", seconds, err)),
+    }
+}
+
+/// Parses the users a client is allowed to run the commands as, given as
+/// CLIENT=USER[,USER...]
+pub fn parse_allowed_users(rule: &str) -> Result<(String, Vec<String>), String> {
+    match rule.split_once('=') {
+        Some((client, users)) if !client.is_empty() && !users.is_empty() => Ok((
+            client.to_string(),
+            users.split(',').map(str::to_string).collect(),
+        )),
+        _ => Err(format!("expected CLIENT=USER[,USER...], got {}", rule)),
+    }
 }
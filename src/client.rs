@@ -1,21 +1,36 @@
 mod cipher;
-mod cli;
+mod cli {
+    pub mod client;
+}
 mod runner;
+mod terminal;
 mod tls;
 
 use anyhow::{anyhow, Context, Result};
-use cli::client::{Cli, Command, Descriptor};
-use std::io::Write;
+use cli::client::{parse_env, Cli, Command, Descriptor, LogPolicy, RunArgs};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use structopt::StructOpt;
 use tls::client_config;
 use tonic::transport::Uri;
 use tonic::transport::{Channel, ClientTlsConfig};
 
 use crate::runner::service::{
-    log_request, log_response, run_request, run_response, runner_client, status_response,
-    LogRequest, RunRequest, StatusRequest, StopRequest,
+    attach_response, list_request, list_response, log_request, log_response, run_follow_response,
+    run_request, run_response, runner_client, stats_response, status_response, stop_request,
+    wait_request, wait_response, watch_response, AttachRequest, DeleteRequest, ListRequest,
+    LogRequest, PauseRequest, ResourceUsage, ResumeRequest, RunRequest, SignalRequest,
+    StatsRequest, StatusRequest, StopRequest, WaitRequest, WatchRequest,
 };
 
+/// The number of chunks of the stdin waiting to be sent to an attached command
+const STDIN_CAPACITY: usize = 16;
+
+/// The maximum size of a single chunk of the stdin sent to an attached command
+const STDIN_BUFFER_SIZE: usize = 4096;
+
 fn main() -> Result<()> {
     pretty_env_logger::init();
 
@@ -25,7 +40,7 @@ fn main() -> Result<()> {
         .unwrap()
         .block_on(async { run().await })
         .map_err(|err| {
-            eprintln!("{}", err.to_string());
+            eprintln!("{}", err);
             std::process::exit(1);
         })
 }
@@ -55,21 +70,130 @@ async fn run() -> Result<()> {
     let mut client = runner_client::RunnerClient::new(channel);
 
     match args.command {
-        Command::Run {
-            memory,
-            disk,
-            cpu,
-            command,
-            args,
-        } => {
+        Command::Run(run) => {
+            let RunArgs {
+                memory,
+                disk,
+                disk_rules,
+                pids,
+                cpu,
+                cpus,
+                cpuset,
+                cpuset_mems,
+                env,
+                env_file,
+                clear_env,
+                cwd,
+                stdin_file,
+                open_stdin,
+                tty,
+                timeout,
+                user,
+                group,
+                supplementary_groups,
+                max_log_size,
+                log_policy,
+                log_segments,
+                follow,
+                command,
+                args,
+            } = *run;
+
+            let log_policy = match log_policy {
+                LogPolicy::Truncate => run_request::LogPolicy::TruncateHead as i32,
+                LogPolicy::Rotate => run_request::LogPolicy::Rotate as i32,
+                LogPolicy::Kill => run_request::LogPolicy::Kill as i32,
+            };
+            let mut variables = HashMap::new();
+
+            if let Some(path) = env_file {
+                variables.extend(read_env_file(&path)?);
+            }
+
+            variables.extend(env);
+
+            let stdin = match stdin_file.as_deref() {
+                Some("-") => {
+                    let mut stdin = Vec::new();
+                    std::io::stdin()
+                        .read_to_end(&mut stdin)
+                        .context("Unable to read the stdin")?;
+                    stdin
+                }
+                Some(path) => std::fs::read(path)
+                    .with_context(|| format!("Unable to read the stdin file {}", path))?,
+                None => Vec::new(),
+            };
+
             let request = tonic::Request::new(RunRequest {
                 command,
                 arguments: args,
+                env: variables,
+                clear_env,
+                working_dir: cwd.unwrap_or_default(),
+                user: user.unwrap_or_default(),
+                group: group.unwrap_or_default(),
+                supplementary_groups,
+                stdin,
+                open_stdin,
+                tty,
+                terminal_size: if tty { terminal::size() } else { None },
+                timeout: timeout.map(run_request::Timeout::TimeoutSeconds),
                 disk: disk.map(run_request::Disk::MaxDisk),
+                disk_rules: disk_rules
+                    .into_iter()
+                    .map(|rule| run_request::DiskRule {
+                        device: rule.device,
+                        read_bps: rule.read_bps,
+                        write_bps: rule.write_bps,
+                        read_iops: rule.read_iops,
+                        write_iops: rule.write_iops,
+                    })
+                    .collect(),
                 memory: memory.map(run_request::Memory::MaxMemory),
                 cpu: cpu.map(run_request::Cpu::MaxCpu),
+                cpu_quota: cpus.map(run_request::CpuQuota::MaxCpus),
+                cpuset_cpus: cpuset.unwrap_or_default(),
+                cpuset_mems: cpuset_mems.unwrap_or_default(),
+                pids: pids.map(run_request::Pids::MaxPids),
+                log_quota: max_log_size.map(run_request::LogQuota::MaxLogSize),
+                log_policy,
+                log_segments: log_segments.unwrap_or(0),
             });
 
+            if follow {
+                let response = client.run_follow(request).await?;
+                let mut inbound = response.into_inner();
+
+                while let Some(item) = inbound.message().await? {
+                    match item.results.unwrap() {
+                        run_follow_response::Results::Id(id) => {
+                            // keeping the stdout for the command's output only
+                            eprintln!("{}", id);
+                        }
+                        run_follow_response::Results::Output(output) => {
+                            write_output(&output)?;
+                        }
+                        run_follow_response::Results::Status(result) => {
+                            std::io::stdout()
+                                .flush()
+                                .context("Unable to flush the stdout")?;
+                            std::process::exit(exit_code(result)?);
+                        }
+                        run_follow_response::Results::RunError(err) => {
+                            return Err(anyhow!("Error: {}", err.description));
+                        }
+                        run_follow_response::Results::LogError(err) => {
+                            return Err(anyhow!("Error: {}", err.description));
+                        }
+                    }
+                }
+
+                return Err(anyhow!(
+                    "Error: the stream ended before the command finished"
+                ));
+            }
+
             let response = client.run(request).await?;
 
             match response.into_inner().results.unwrap() {
@@ -80,8 +204,16 @@ async fn run() -> Result<()> {
                 run_response::Results::Error(err) => Err(anyhow!("Error: {}", err.description)),
             }
         }
-        Command::Stop { id } => {
-            let request = tonic::Request::new(StopRequest { id: id.to_string() });
+        Command::Stop {
+            id,
+            signal,
+            grace_period,
+        } => {
+            let request = tonic::Request::new(StopRequest {
+                id: id.to_string(),
+                signal: signal.unwrap_or(0),
+                grace_period: grace_period.map(stop_request::GracePeriod::GracePeriodSeconds),
+            });
 
             let response = client.stop(request).await?;
 
@@ -93,55 +225,377 @@ async fn run() -> Result<()> {
                 }
             }
         }
+        Command::Signal { id, signal, group } => {
+            let request = tonic::Request::new(SignalRequest {
+                id: id.to_string(),
+                signal,
+                group,
+            });
+
+            let response = client.signal(request).await?;
+
+            match response.into_inner().error {
+                Some(err) => Err(anyhow!("Error: {}", err.description)),
+                None => {
+                    println!("Sent");
+                    Ok(())
+                }
+            }
+        }
+        Command::Pause { id } => {
+            let request = tonic::Request::new(PauseRequest { id: id.to_string() });
+
+            let response = client.pause(request).await?;
+
+            match response.into_inner().error {
+                Some(err) => Err(anyhow!("Error: {}", err.description)),
+                None => {
+                    println!("Paused");
+                    Ok(())
+                }
+            }
+        }
+        Command::Resume { id } => {
+            let request = tonic::Request::new(ResumeRequest { id: id.to_string() });
+
+            let response = client.resume(request).await?;
+
+            match response.into_inner().error {
+                Some(err) => Err(anyhow!("Error: {}", err.description)),
+                None => {
+                    println!("Resumed");
+                    Ok(())
+                }
+            }
+        }
+        Command::Attach {
+            id,
+            tail,
+            detach_keys,
+        } => {
+            let (sender, receiver) = tokio::sync::mpsc::channel(STDIN_CAPACITY);
+            let stdin_closed = Arc::new(AtomicBool::new(false));
+            let (detach, mut detached) = tokio::sync::oneshot::channel();
+
+            sender
+                .send(AttachRequest {
+                    id: id.to_string(),
+                    tail_lines: tail.unwrap_or(0),
+                    ..Default::default()
+                })
+                .await?;
+
+            let outbound = futures::stream::unfold(receiver, |mut receiver| async move {
+                receiver.recv().await.map(|request| (request, receiver))
+            });
+
+            let response = client.attach(outbound).await?;
+            let mut inbound = response.into_inner();
+
+            // the stdin only gets forwarded once it's known whether the
+            // command runs in a pseudo-terminal
+            let mut stdin = Some((sender, detach));
+            let mut raw_mode = None;
+
+            loop {
+                let item = tokio::select! {
+                    item = inbound.message() => match item? {
+                        Some(item) => item,
+                        None => break,
+                    },
+                    Ok(()) = &mut detached => {
+                        drop(raw_mode);
+                        eprintln!("Detached");
+                        return Ok(());
+                    }
+                };
+
+                match item.results.unwrap() {
+                    attach_response::Results::Tty(tty) => {
+                        let (sender, detach) = match stdin.take() {
+                            Some(stdin) => stdin,
+                            None => continue,
+                        };
+
+                        // a command without a pseudo-terminal reads the stdin
+                        // as is, lines included
+                        let detach_keys = if tty && terminal::is_terminal() {
+                            raw_mode = Some(terminal::RawMode::enable()?);
+                            tokio::spawn(forward_resizes(sender.clone()));
+                            Some(detach_keys.0.clone())
+                        } else {
+                            None
+                        };
+
+                        let closed = Arc::clone(&stdin_closed);
+
+                        // the reads of the stdin block, so they get a thread
+                        // of their own
+                        std::thread::spawn(move || {
+                            forward_stdin(&sender, &closed, detach_keys, detach)
+                        });
+                    }
+                    attach_response::Results::Output(output) => {
+                        write_output(&output)?;
+                    }
+                    attach_response::Results::Status(result) => {
+                        std::io::stdout()
+                            .flush()
+                            .context("Unable to flush the stdout")?;
+                        drop(raw_mode);
+                        std::process::exit(exit_code(result)?);
+                    }
+                    attach_response::Results::Error(err) => {
+                        let closed = attach_response::attach_error::Errors::AttachError(
+                            attach_response::attach_error::Error::StdinClosedError as i32,
+                        );
+
+                        // the output is still worth streaming
+                        if err.errors == Some(closed) {
+                            if !stdin_closed.swap(true, Ordering::SeqCst) {
+                                eprintln!("Error: {}", err.description);
+                            }
+                        } else {
+                            return Err(anyhow!("Error: {}", err.description));
+                        }
+                    }
+                    attach_response::Results::LogError(err) => {
+                        return Err(anyhow!("Error: {}", err.description));
+                    }
+                }
+            }
+
+            drop(raw_mode);
+
+            Err(anyhow!(
+                "Error: the stream ended before the command finished"
+            ))
+        }
+        Command::Delete { id } => {
+            let request = tonic::Request::new(DeleteRequest { id: id.to_string() });
+
+            let response = client.delete(request).await?;
+
+            match response.into_inner().error {
+                Some(err) => Err(anyhow!("Error: {}", err.description)),
+                None => {
+                    println!("Deleted");
+                    Ok(())
+                }
+            }
+        }
         Command::Status { id } => {
             let request = tonic::Request::new(StatusRequest { id: id.to_string() });
 
             let response = client.status(request).await?;
 
             match response.into_inner().results.unwrap() {
-                status_response::Results::Result(result) => match result.finish {
-                    Some(status_response::status_result::Finish::Result(exit_result)) => {
-                        if let Some(status_response::status_result::exit_result::Exit::Code(code)) =
-                            exit_result.exit
-                        {
-                            println!("Exited with code: {}", code);
-                        } else if let Some(
-                            status_response::status_result::exit_result::Kill::Signal(signal),
-                        ) = exit_result.kill
-                        {
-                            println!("Killed with signal: {}", signal);
-                        } else {
-                            println!("Stopped but no exit code or signal is known");
+                status_response::Results::Result(result) => {
+                    print_status(&result);
+                    Ok(())
+                }
+                status_response::Results::Error(err) => Err(anyhow!("Error: {}", err.description)),
+            }
+        }
+        Command::Stats {
+            id,
+            watch,
+            interval,
+        } => {
+            let request = tonic::Request::new(StatsRequest {
+                id: id.to_string(),
+                interval_ms: interval,
+            });
+
+            if watch {
+                let response = client.stats_follow(request).await?;
+                let mut inbound = response.into_inner();
+
+                let mut first = true;
+
+                while let Some(item) = inbound.message().await? {
+                    match item.results.unwrap() {
+                        stats_response::Results::Result(usage) => {
+                            if first {
+                                print_usage_header();
+                                first = false;
+                            }
+
+                            print_usage(&usage);
+                        }
+                        stats_response::Results::Error(err) => {
+                            return Err(anyhow!("Error: {}", err.description));
                         }
-                        Ok(())
                     }
-                    None => {
-                        println!("Running");
+                }
+
+                Ok(())
+            } else {
+                let response = client.stats(request).await?;
+
+                match response.into_inner().results.unwrap() {
+                    stats_response::Results::Result(usage) => {
+                        print_usage_header();
+                        print_usage(&usage);
                         Ok(())
                     }
-                },
-                status_response::Results::Error(err) => Err(anyhow!("Error: {}", err.description)),
+                    stats_response::Results::Error(err) => {
+                        Err(anyhow!("Error: {}", err.description))
+                    }
+                }
+            }
+        }
+        Command::Wait { id, timeout } => {
+            let request = tonic::Request::new(WaitRequest {
+                id: id.to_string(),
+                timeout: timeout.map(wait_request::Timeout::TimeoutSeconds),
+            });
+
+            let response = client.wait(request).await?;
+
+            match response.into_inner().results.unwrap() {
+                wait_response::Results::Result(result) => {
+                    std::process::exit(exit_code(result)?);
+                }
+                wait_response::Results::Error(err) => Err(anyhow!("Error: {}", err.description)),
             }
         }
-        Command::Log { id, descriptor } => {
+        Command::List {
+            running,
+            stopped,
+            paused,
+            command,
+            started_after,
+            page_size,
+            page_token,
+        } => {
+            let state = if running {
+                list_request::State::Running
+            } else if stopped {
+                list_request::State::Stopped
+            } else if paused {
+                list_request::State::Paused
+            } else {
+                list_request::State::Any
+            };
+
+            let request = tonic::Request::new(ListRequest {
+                state: state as i32,
+                command_prefix: command.unwrap_or_default(),
+                started_after: started_after.map_or(0, |seconds| seconds * 1000),
+                page_size: page_size.unwrap_or(0),
+                page_token: page_token.unwrap_or_default(),
+            });
+
+            let response = client.list(request).await?;
+
+            match response.into_inner().results.unwrap() {
+                list_response::Results::Result(list) => {
+                    println!(
+                        "{:<36}  {:<7}  {:<19}  {:<19}  {:<9}  COMMAND",
+                        "ID", "STATE", "STARTED", "FINISHED", "RESULT"
+                    );
+
+                    for job in list.jobs {
+                        let (state, result) = describe_status(job.status);
+                        let finished = match job.finish {
+                            Some(list_response::job::Finish::FinishedAt(finished_at)) => {
+                                format_time(finished_at)
+                            }
+                            None => "-".to_string(),
+                        };
+
+                        let mut command = vec![job.command];
+                        command.extend(job.arguments);
+
+                        println!(
+                            "{:<36}  {:<7}  {:<19}  {:<19}  {:<9}  {}",
+                            job.id,
+                            state,
+                            format_time(job.started_at),
+                            finished,
+                            result,
+                            command.join(" ")
+                        );
+                    }
+
+                    if !list.next_page_token.is_empty() {
+                        eprintln!(
+                            "More commands available, use --page-token {}",
+                            list.next_page_token
+                        );
+                    }
+
+                    Ok(())
+                }
+                list_response::Results::Error(err) => Err(anyhow!("Error: {}", err.description)),
+            }
+        }
+        Command::Watch { ids } => {
+            let request = tonic::Request::new(WatchRequest {
+                ids: ids.iter().map(|id| id.to_string()).collect(),
+            });
+
+            let response = client.watch(request).await?;
+            let mut inbound = response.into_inner();
+
+            while let Some(item) = inbound.message().await? {
+                match item.results.unwrap() {
+                    watch_response::Results::Event(event) => {
+                        println!(
+                            "{}  {}  {}",
+                            format_time(event.time),
+                            event.id,
+                            describe_event(&event)
+                        );
+                    }
+                    watch_response::Results::Error(err) => {
+                        return Err(anyhow!("Error: {}", err.description));
+                    }
+                }
+            }
+
+            Ok(())
+        }
+        Command::Log {
+            id,
+            descriptor,
+            tail,
+            offset,
+            no_follow,
+        } => {
             let descriptor = match descriptor {
                 Descriptor::Stdout => log_request::Descriptor::Stdout as i32,
                 Descriptor::Stderr => log_request::Descriptor::Stderr as i32,
+                Descriptor::Both => log_request::Descriptor::Both as i32,
             };
             let request = tonic::Request::new(LogRequest {
                 id: id.to_string(),
                 descriptor,
+                start_offset: offset.unwrap_or(0),
+                tail_lines: tail.unwrap_or(0),
+                follow: !no_follow,
             });
 
             let response = client.log(request).await?;
             let mut inbound = response.into_inner();
             let mut out = std::io::stdout();
+            let mut err_out = std::io::stderr();
 
             while let Some(item) = inbound.message().await? {
                 match item.results.unwrap() {
-                    log_response::Results::Data(data) => {
-                        out.write_all(&data)
-                            .context("Unable to write data into the stdout")?;
+                    log_response::Results::Chunk(chunk) => {
+                        // the chunks of both descriptors are written out
+                        // to their local counterparts:
+                        if chunk.descriptor == log_request::Descriptor::Stderr as i32 {
+                            out.flush().context("Unable to flush the stdout")?;
+                            err_out
+                                .write_all(&chunk.data)
+                                .context("Unable to write data into the stderr")?;
+                        } else {
+                            out.write_all(&chunk.data)
+                                .context("Unable to write data into the stdout")?;
+                        }
                     }
                     log_response::Results::Error(err) => {
                         return Err(anyhow!("Error: {}", err.description));
@@ -153,3 +607,337 @@ async fn run() -> Result<()> {
         }
     }
 }
+
+/// Writes a chunk of the output of a command into the stdout or the stderr,
+/// whichever it came from
+fn write_output(output: &run_follow_response::Output) -> Result<()> {
+    let mut out = std::io::stdout();
+
+    if output.descriptor == log_request::Descriptor::Stderr as i32 {
+        out.flush().context("Unable to flush the stdout")?;
+        std::io::stderr()
+            .write_all(&output.data)
+            .context("Unable to write data into the stderr")?;
+    } else {
+        out.write_all(&output.data)
+            .context("Unable to write data into the stdout")?;
+    }
+
+    Ok(())
+}
+
+/// Sends the chunks read from the stdin to an attached command until the end
+/// of the stdin, closing the command's stdin then, or until the command's
+/// stdin gets closed. When given the detach keys, it stops as soon as they
+/// get typed instead, leaving the command's stdin open
+fn forward_stdin(
+    sender: &tokio::sync::mpsc::Sender<AttachRequest>,
+    closed: &AtomicBool,
+    detach_keys: Option<Vec<u8>>,
+    detach: tokio::sync::oneshot::Sender<()>,
+) {
+    let mut buffer = vec![0_u8; STDIN_BUFFER_SIZE];
+    let mut stdin = std::io::stdin();
+    let mut detach_keys = detach_keys.map(|keys| DetachSequence { keys, typed: 0 });
+
+    while !closed.load(Ordering::SeqCst) {
+        let request = match stdin.read(&mut buffer) {
+            Ok(0) | Err(_) => AttachRequest {
+                close_stdin: true,
+                ..Default::default()
+            },
+            Ok(bytes) => {
+                let input = match &mut detach_keys {
+                    Some(sequence) => match sequence.filter(&buffer[..bytes]) {
+                        Some(input) => input,
+                        None => {
+                            detach.send(()).ok();
+                            return;
+                        }
+                    },
+                    None => buffer[..bytes].to_vec(),
+                };
+
+                AttachRequest {
+                    input,
+                    ..Default::default()
+                }
+            }
+        };
+
+        let last = request.close_stdin;
+
+        if sender.blocking_send(request).is_err() || last {
+            break;
+        }
+    }
+}
+
+/// The detach keys along with how many of them were typed last, which are
+/// held back until it's known whether the whole sequence gets typed
+struct DetachSequence {
+    keys: Vec<u8>,
+    typed: usize,
+}
+
+impl DetachSequence {
+    /// Returns the input to send to the command, None once the whole
+    /// sequence gets typed
+    fn filter(&mut self, bytes: &[u8]) -> Option<Vec<u8>> {
+        let mut input = Vec::with_capacity(bytes.len());
+
+        for &byte in bytes {
+            if byte != self.keys[self.typed] {
+                // the keys typed so far turned out to be meant for the command
+                input.extend_from_slice(&self.keys[..self.typed]);
+                self.typed = 0;
+            }
+
+            if byte == self.keys[self.typed] {
+                self.typed += 1;
+
+                if self.typed == self.keys.len() {
+                    return None;
+                }
+            } else {
+                input.push(byte);
+            }
+        }
+
+        Some(input)
+    }
+}
+
+/// Sends the size of the local terminal to an attached command running in a
+/// pseudo-terminal, and then again every time the local terminal gets resized
+async fn forward_resizes(sender: tokio::sync::mpsc::Sender<AttachRequest>) -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut resized = signal(SignalKind::window_change())?;
+
+    loop {
+        if let Some(size) = terminal::size() {
+            let request = AttachRequest {
+                resize: Some(size),
+                ..Default::default()
+            };
+
+            if sender.send(request).await.is_err() {
+                return Ok(());
+            }
+        }
+
+        if resized.recv().await.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+/// Returns the exit code mirroring the one of a finished command, following
+/// the shell convention of 128 + signal for the killed ones
+fn exit_code(status: status_response::StatusResult) -> Result<i32> {
+    use status_response::status_result::{exit_result, Finish};
+
+    match status.finish {
+        Some(Finish::Result(exit_result)) => {
+            if let Some(exit_result::Exit::Code(code)) = exit_result.exit {
+                Ok(code)
+            } else if let Some(exit_result::Kill::Signal(signal)) = exit_result.kill {
+                Ok(128 + signal)
+            } else {
+                Err(anyhow!("Error: the exit status of the command is unknown"))
+            }
+        }
+        None => Err(anyhow!("Error: the command is still running")),
+    }
+}
+
+/// Reads the environment variables from a file made of KEY=VALUE lines,
+/// skipping the empty ones and the comments starting with #
+fn read_env_file(path: &str) -> Result<Vec<(String, String)>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Couldn't read the environment file {}", path))?;
+
+    contents
+        .lines()
+        .enumerate()
+        .map(|(number, line)| (number, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            parse_env(line).map_err(|err| anyhow!("Error in {}:{}: {}", path, number + 1, err))
+        })
+        .collect()
+}
+
+/// Prints the status of a command for the status view
+fn print_status(status: &status_response::StatusResult) {
+    use status_response::status_result::{exit_result, Finish};
+
+    match &status.finish {
+        Some(Finish::Result(exit_result)) => {
+            if exit_result.lost {
+                println!("Lost (exit status unknown)");
+            } else if let Some(exit_result::Exit::Code(code)) = exit_result.exit {
+                match exit_result::Reason::from_i32(exit_result.reason) {
+                    Some(exit_result::Reason::TimedOut) => {
+                        println!("Timed out, exited with code: {}", code)
+                    }
+                    _ => println!("Exited with code: {}", code),
+                }
+            } else if let Some(exit_result::Kill::Signal(signal)) = exit_result.kill {
+                match exit_result::Reason::from_i32(exit_result.reason) {
+                    Some(exit_result::Reason::OomKilled) => {
+                        println!("Killed by the OOM killer with signal: {}", signal)
+                    }
+                    Some(exit_result::Reason::TimedOut) => {
+                        println!("Timed out, killed with signal: {}", signal)
+                    }
+                    Some(exit_result::Reason::LogQuotaExceeded) => {
+                        println!(
+                            "Killed for going over the log quota with signal: {}",
+                            signal
+                        )
+                    }
+                    _ => println!("Killed with signal: {}", signal),
+                }
+            } else {
+                println!("Stopped but no exit code or signal is known");
+            }
+
+            if exit_result.peak_memory > 0 {
+                println!("Peak memory usage: {} bytes", exit_result.peak_memory);
+            }
+
+            if let Some(usage) = &exit_result.usage {
+                println!(
+                    "CPU time: {}, disk read: {} bytes, disk written: {} bytes",
+                    format_cpu_time(usage.cpu_time),
+                    usage.io_read_bytes,
+                    usage.io_write_bytes
+                );
+            }
+        }
+        None if status.paused => println!("Paused"),
+        None => println!("Running"),
+    }
+
+    if status.pids_limit_hits > 0 {
+        println!(
+            "Hit the pids limit: {} time(s) creating a process failed",
+            status.pids_limit_hits
+        );
+    }
+}
+
+/// Prints the header of the stats view
+fn print_usage_header() {
+    println!(
+        "{:>12}  {:>12}  {:>12}  {:>12}  {:>12}  {:>5}",
+        "CPU TIME", "MEMORY", "PEAK MEMORY", "DISK READ", "DISK WRITTEN", "TASKS"
+    );
+}
+
+/// Prints a sample of the resource usage for the stats view
+fn print_usage(usage: &ResourceUsage) {
+    println!(
+        "{:>12}  {:>12}  {:>12}  {:>12}  {:>12}  {:>5}",
+        format_cpu_time(usage.cpu_time),
+        usage.memory,
+        usage.peak_memory,
+        usage.io_read_bytes,
+        usage.io_write_bytes,
+        usage.tasks
+    );
+}
+
+/// Formats the CPU time given in nanoseconds as seconds
+fn format_cpu_time(nanos: u64) -> String {
+    format!("{:.3}s", nanos as f64 / 1e9)
+}
+
+/// Returns the state and the result of a command for the list view
+fn describe_status(status: Option<status_response::StatusResult>) -> (&'static str, String) {
+    use status_response::status_result::{exit_result, Finish};
+
+    if matches!(&status, Some(status) if status.paused) {
+        return ("Paused", "-".to_string());
+    }
+
+    match status.and_then(|status| status.finish) {
+        Some(Finish::Result(exit_result)) => {
+            if exit_result.lost {
+                ("Lost", "-".to_string())
+            } else if let Some(exit_result::Exit::Code(code)) = exit_result.exit {
+                match exit_result::Reason::from_i32(exit_result.reason) {
+                    Some(exit_result::Reason::TimedOut) => {
+                        ("Stopped", format!("code {} (timeout)", code))
+                    }
+                    _ => ("Stopped", format!("code {}", code)),
+                }
+            } else if let Some(exit_result::Kill::Signal(signal)) = exit_result.kill {
+                match exit_result::Reason::from_i32(exit_result.reason) {
+                    Some(exit_result::Reason::OomKilled) => {
+                        ("Stopped", format!("signal {} (OOM)", signal))
+                    }
+                    Some(exit_result::Reason::TimedOut) => {
+                        ("Stopped", format!("signal {} (timeout)", signal))
+                    }
+                    Some(exit_result::Reason::LogQuotaExceeded) => {
+                        ("Stopped", format!("signal {} (log quota)", signal))
+                    }
+                    _ => ("Stopped", format!("signal {}", signal)),
+                }
+            } else {
+                ("Stopped", "-".to_string())
+            }
+        }
+        None => ("Running", "-".to_string()),
+    }
+}
+
+/// Returns the description of a lifecycle event for the watch view
+fn describe_event(event: &watch_response::Event) -> String {
+    use watch_response::event::Kind;
+
+    match Kind::from_i32(event.kind) {
+        Some(Kind::Started) => "Started".to_string(),
+        Some(Kind::Exited) => format!("Exited with code: {}", event.exit_code),
+        Some(Kind::Killed) => format!("Killed with signal: {}", event.signal),
+        Some(Kind::OomKilled) => format!("Killed by the OOM killer with signal: {}", event.signal),
+        Some(Kind::StopRequested) => "Stop requested".to_string(),
+        Some(Kind::Lost) => "Lost (exit status unknown)".to_string(),
+        Some(Kind::Paused) => "Paused".to_string(),
+        Some(Kind::Resumed) => "Resumed".to_string(),
+        Some(Kind::TimedOut) => "Timed out, stopping".to_string(),
+        None => "Unknown event".to_string(),
+    }
+}
+
+/// Formats milliseconds since the UNIX epoch as UTC date and time
+fn format_time(millis: u64) -> String {
+    let seconds = millis / 1000;
+    let days = (seconds / 86400) as i64;
+    let time = seconds % 86400;
+
+    // converts days since the epoch into the civil date, see:
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
@@ -0,0 +1,347 @@
+mod v1;
+mod v2;
+
+use crate::runner::service::{
+    run_request,
+    run_response::{run_error, RunError},
+    ResourceUsage, RunRequest,
+};
+use anyhow::Result;
+use controlgroup::v1::cpuset::IdSet;
+use nix::errno::Errno;
+use nix::libc;
+use nix::sys::signal::{kill, Signal};
+use nix::sys::stat::{major, minor};
+use nix::unistd::Pid;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tokio::process::Command;
+use uuid::Uuid;
+use v1::V1Cgroups;
+use v2::V2Cgroups;
+
+/// Where the unified hierarchy is mounted when it's the only one
+const UNIFIED_ROOT: &str = "/sys/fs/cgroup";
+
+/// The period of the CPU time the CPU quota applies to, in microseconds
+const CFS_PERIOD_US: u64 = 100_000;
+
+/// The least CPU time within the period the kernel accepts as the quota
+const MIN_CFS_QUOTA_US: u64 = 1_000;
+
+/// Disk IO limits of a single device, 0 meaning no limit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeviceLimits {
+    pub major: u16,
+    pub minor: u16,
+    pub read_bps: u64,
+    pub write_bps: u64,
+    pub read_iops: u64,
+    pub write_iops: u64,
+}
+
+/// What the control groups tell about the processes in them, as of their exit
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Accounting {
+    pub oom_killed: bool,
+    pub pids_limit_hits: u64,
+    pub usage: ResourceUsage,
+}
+
+/// A disk device found by udev
+struct Disk {
+    major: u16,
+    minor: u16,
+    devnode: Option<PathBuf>,
+}
+
+/// Control groups constraining the resources of a single process. They are
+/// created in whichever of the hierarchies is mounted in the system
+pub trait Cgroups: Send + Sync {
+    /// Makes the command join the control groups right before it gets executed
+    fn apply_pre_exec(&self, cmd: &mut Command);
+
+    /// Checks if any of the processes in the control groups got killed by the
+    /// OOM killer. Has to be called before the control groups are deleted
+    fn oom_killed(&self) -> bool;
+
+    /// Reads the counters of the resources used by the processes in the
+    /// control groups so far, all 0 when the processes aren't constrained.
+    /// Has to be called before the control groups are deleted
+    fn usage(&self) -> ResourceUsage;
+
+    /// Returns the number of times creating a new task in the control groups
+    /// failed because of the pids limit. Has to be called before the control
+    /// groups are deleted
+    fn pids_limit_hits(&self) -> u64;
+
+    /// Collects all of the above at once. Has to be called before the control
+    /// groups are deleted
+    fn accounting(&self) -> Accounting {
+        Accounting {
+            oom_killed: self.oom_killed(),
+            pids_limit_hits: self.pids_limit_hits(),
+            usage: self.usage(),
+        }
+    }
+
+    /// Returns the PIDs of the processes in the control groups, none when
+    /// the processes aren't constrained
+    fn procs(&self) -> Vec<u32>;
+
+    /// Kills all of the processes in the control groups with SIGKILL,
+    /// including the ones forked in the meantime
+    fn kill(&mut self) -> Result<()>;
+
+    /// Freezes the processes in the control groups, pausing them until
+    /// they're thawed
+    fn freeze(&mut self) -> Result<()>;
+
+    /// Thaws the processes frozen before, resuming them
+    fn thaw(&mut self) -> Result<()>;
+
+    /// Tells if the processes in the control groups are frozen or are
+    /// being frozen
+    fn is_frozen(&self) -> bool;
+
+    /// Removes the control groups once their processes are gone
+    fn delete(&mut self) -> Result<()>;
+}
+
+/// Creates the control groups constraining the process started with a given
+/// request, with the disk IO limits resolved from it by device_limits
+pub fn create_cgroups(
+    request: &RunRequest,
+    id: &Uuid,
+    devices: &[DeviceLimits],
+) -> Result<Box<dyn Cgroups>> {
+    if is_unified() {
+        Ok(Box::new(V2Cgroups::create(request, id, devices)?))
+    } else {
+        Ok(Box::new(V1Cgroups::create(request, id, devices)?))
+    }
+}
+
+/// Represents control groups that were created for a given request
+/// by one of the previous instances of the server
+pub fn existing_cgroups(request: &RunRequest, id: &Uuid) -> Box<dyn Cgroups> {
+    if is_unified() {
+        Box::new(V2Cgroups::existing(request, id))
+    } else {
+        Box::new(V1Cgroups::existing(request, id))
+    }
+}
+
+/// Tells if the process started with a given request is constrained by any
+/// of the control groups
+pub fn is_constrained(request: &RunRequest) -> bool {
+    request.memory.is_some()
+        || request.cpu.is_some()
+        || request.disk.is_some()
+        || !request.disk_rules.is_empty()
+        || request.cpu_quota.is_some()
+        || has_cpuset(request)
+        || request.pids.is_some()
+}
+
+/// Tells if the process started with a given request is pinned to some of
+/// the CPUs or the memory nodes
+fn has_cpuset(request: &RunRequest) -> bool {
+    !request.cpuset_cpus.is_empty() || !request.cpuset_mems.is_empty()
+}
+
+/// Checks if the CPU quota of a given request is one the kernel accepts and
+/// isn't over the CPUs the process can run on, as it would never limit it
+pub fn is_valid_cpu_quota(request: &RunRequest) -> bool {
+    match request.cpu_quota {
+        Some(run_request::CpuQuota::MaxCpus(cpus)) => {
+            cpus.is_finite()
+                && cfs_quota_us(cpus) >= MIN_CFS_QUOTA_US
+                && cpus <= available_cpus(request)
+        }
+        None => true,
+    }
+}
+
+/// Returns the number of the CPUs a given request can run on: the ones of
+/// its cpuset when it's given, all the CPUs online otherwise
+fn available_cpus(request: &RunRequest) -> f64 {
+    let online = online_cpus();
+
+    if request.cpuset_cpus.is_empty() {
+        return online;
+    }
+
+    match request.cpuset_cpus.parse::<IdSet>() {
+        Ok(cpus) => online.min(cpus.to_hash_set().len() as f64),
+        Err(_) => online,
+    }
+}
+
+/// Returns the number of the CPUs online, a single one when it can't be told.
+/// It's only read once, as it's checked on every run
+fn online_cpus() -> f64 {
+    static ONLINE_CPUS: OnceLock<f64> = OnceLock::new();
+
+    *ONLINE_CPUS.get_or_init(
+        || match unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) } {
+            cpus if cpus > 0 => cpus as f64,
+            _ => 1.0,
+        },
+    )
+}
+
+/// Checks if the CPUs and the memory nodes of a given request are given as
+/// lists of ids (or not given at all)
+pub fn is_valid_cpuset(request: &RunRequest) -> bool {
+    [&request.cpuset_cpus, &request.cpuset_mems]
+        .iter()
+        .all(|list| list.is_empty() || list.parse::<IdSet>().is_ok())
+}
+
+/// Returns the CPU time within the period the process can use given the
+/// number of CPUs
+fn cfs_quota_us(cpus: f64) -> u64 {
+    (cpus * CFS_PERIOD_US as f64).round() as u64
+}
+
+/// Parses the PIDs listed in a given cgroup.procs file, none when it can't
+/// be read
+fn read_procs(path: &Path) -> Vec<u32> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|pid| pid.trim().parse().ok())
+        .collect()
+}
+
+/// Sends SIGKILL to each of the given processes, skipping the ones that
+/// are already gone
+fn kill_procs(pids: &[u32]) -> Result<()> {
+    for pid in pids {
+        match kill(Pid::from_raw(*pid as i32), Signal::SIGKILL) {
+            Ok(_) | Err(nix::Error::Sys(Errno::ESRCH)) => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks if the unified (v2) hierarchy is the only one mounted. The v1
+/// hierarchies are used otherwise, also in the hybrid mode
+fn is_unified() -> bool {
+    Path::new(UNIFIED_ROOT).join("cgroup.controllers").exists()
+}
+
+/// Resolves the disk rules of a request against the disks found by udev
+/// into the limits of each of the devices. The max disk value applies to
+/// the reads and the writes of all of the disks without a rule of their own
+pub fn device_limits(request: &RunRequest) -> Result<Vec<DeviceLimits>, RunError> {
+    if request.disk.is_none() && request.disk_rules.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let disks = disks()?;
+
+    let mut limits = match request.disk {
+        Some(run_request::Disk::MaxDisk(max)) => disks
+            .iter()
+            .map(|disk| DeviceLimits {
+                major: disk.major,
+                minor: disk.minor,
+                read_bps: max,
+                write_bps: max,
+                read_iops: 0,
+                write_iops: 0,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let mut ruled = Vec::new();
+
+    for rule in &request.disk_rules {
+        let disk = find_disk(&disks, &rule.device)?;
+
+        if rule.read_bps == 0 && rule.write_bps == 0 && rule.read_iops == 0 && rule.write_iops == 0
+        {
+            return Err(RunError::with_details(
+                run_error::Error::InvalidDiskRuleError,
+                &format!("no limits given for {}", rule.device),
+            ));
+        }
+
+        if ruled.contains(&(disk.major, disk.minor)) {
+            return Err(RunError::with_details(
+                run_error::Error::InvalidDiskRuleError,
+                &format!("{} given more than once", rule.device),
+            ));
+        }
+
+        ruled.push((disk.major, disk.minor));
+        limits.retain(|limit| (limit.major, limit.minor) != (disk.major, disk.minor));
+        limits.push(DeviceLimits {
+            major: disk.major,
+            minor: disk.minor,
+            read_bps: rule.read_bps,
+            write_bps: rule.write_bps,
+            read_iops: rule.read_iops,
+            write_iops: rule.write_iops,
+        });
+    }
+
+    Ok(limits)
+}
+
+/// Finds the disk given either by its path or by its major:minor numbers
+fn find_disk<'a>(disks: &'a [Disk], device: &str) -> Result<&'a Disk, RunError> {
+    let unknown = || RunError::with_details(run_error::Error::UnknownDeviceError, device);
+
+    let found = match parse_numbers(device) {
+        Some((major, minor)) => disks
+            .iter()
+            .find(|disk| disk.major == major && disk.minor == minor),
+        None => {
+            // resolves the symlinks like /dev/disk/by-id/...
+            let path = std::fs::canonicalize(device).map_err(|_| unknown())?;
+
+            disks
+                .iter()
+                .find(|disk| disk.devnode.as_deref() == Some(path.as_path()))
+        }
+    };
+
+    found.ok_or_else(unknown)
+}
+
+/// Parses the device numbers given as major:minor
+fn parse_numbers(device: &str) -> Option<(u16, u16)> {
+    let (major, minor) = device.split_once(':')?;
+
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// Returns the disk devices found by udev
+fn disks() -> Result<Vec<Disk>> {
+    let mut enumerator = udev::Enumerator::new()?;
+    enumerator.match_subsystem("block")?;
+
+    let devices = enumerator
+        .scan_devices()?
+        .filter_map(|device| {
+            let is_disk = device.devtype().and_then(|devtype| devtype.to_str()) == Some("disk");
+
+            match device.devnum() {
+                Some(devnum) if is_disk => Some(Disk {
+                    major: major(devnum) as u16,
+                    minor: minor(devnum) as u16,
+                    devnode: device.devnode().map(Path::to_path_buf),
+                }),
+                _ => None,
+            }
+        })
+        .collect();
+
+    Ok(devices)
+}
@@ -0,0 +1,318 @@
+use crate::runner::cgroups::{
+    cfs_quota_us, has_cpuset, is_constrained, kill_procs, read_procs, Cgroups, DeviceLimits,
+    CFS_PERIOD_US,
+};
+use crate::runner::service::{run_request, ResourceUsage, RunRequest};
+
+use controlgroup::v1::{Builder, SubsystemKind, UnifiedRepr};
+
+use anyhow::{anyhow, Context, Result};
+use controlgroup::v1::cpuset::IdSet;
+use controlgroup::v1::freezer::State;
+use controlgroup::v1::Cgroup;
+use controlgroup::{Device, Max};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use uuid::Uuid;
+
+/// Where the hierarchy of the cpuset subsystem is mounted
+const CPUSET_ROOT: &str = "/sys/fs/cgroup/cpuset";
+
+/// Where the hierarchies of the cpu and cpuacct subsystems are mounted. They
+/// are often the same one, linked from both
+const CPU_ROOT: &str = "/sys/fs/cgroup/cpu";
+const CPUACCT_ROOT: &str = "/sys/fs/cgroup/cpuacct";
+
+/// Subsystems the constrained processes are put into, whether or not they're
+/// constrained by them, for the sake of accounting their usage of the resources.
+/// They're also put into the freezer subsystem so that they can be paused
+const ACCOUNTED_SUBSYSTEMS: [SubsystemKind; 5] = [
+    SubsystemKind::Cpu,
+    SubsystemKind::Cpuacct,
+    SubsystemKind::Memory,
+    SubsystemKind::BlkIo,
+    SubsystemKind::Pids,
+];
+
+/// Control groups of a process in the v1 hierarchies, one per subsystem
+pub struct V1Cgroups {
+    cgroups: UnifiedRepr,
+}
+
+impl V1Cgroups {
+    pub fn create(request: &RunRequest, id: &Uuid, devices: &[DeviceLimits]) -> Result<V1Cgroups> {
+        let mut builder = Builder::new(PathBuf::from(id.to_string()));
+
+        if let Some(run_request::Memory::MaxMemory(max)) = request.memory {
+            builder = builder.memory().limit_in_bytes(max).done();
+        }
+
+        if let Some(run_request::Cpu::MaxCpu(max)) = request.cpu {
+            builder = builder.cpu().shares(max).done();
+        }
+
+        if let Some(run_request::CpuQuota::MaxCpus(cpus)) = request.cpu_quota {
+            builder = builder
+                .cpu()
+                .cfs_period_us(CFS_PERIOD_US)
+                .cfs_quota_us(cfs_quota_us(cpus) as i64)
+                .done();
+        }
+
+        if has_cpuset(request) {
+            // a new cpuset starts with no CPUs nor memory nodes, so the ones
+            // that weren't requested are taken from the root
+            builder = builder
+                .cpuset()
+                .cpus(inherited(&request.cpuset_cpus, "cpuset.cpus")?)
+                .mems(inherited(&request.cpuset_mems, "cpuset.mems")?)
+                .done();
+        }
+
+        if let Some(run_request::Pids::MaxPids(max)) = request.pids {
+            builder = builder.pids().max(Max::Limit(max)).done();
+        }
+
+        if !devices.is_empty() {
+            // The blkio.weight and similar are often disabled on some
+            // of the latest distros (Ubuntu 20.04 being one example)
+            // so the disk IO gets throttled per device instead
+            builder = builder
+                .blkio()
+                .read_bps_device(throttled(devices, |limits| limits.read_bps))
+                .write_bps_device(throttled(devices, |limits| limits.write_bps))
+                .read_iops_device(throttled(devices, |limits| limits.read_iops))
+                .write_iops_device(throttled(devices, |limits| limits.write_iops))
+                .done();
+        }
+
+        if is_constrained(request) {
+            builder = builder
+                .cpu()
+                .done()
+                .cpuacct()
+                .memory()
+                .done()
+                .blkio()
+                .done()
+                .pids()
+                .done()
+                .freezer()
+                .done()
+                .skip_create(skipped_subsystems());
+        }
+
+        let cgroups = builder
+            .build()
+            .context("Couldn't create a Linux control group for the new process")?;
+
+        Ok(V1Cgroups { cgroups })
+    }
+
+    /// Represents control groups that were created for a given request
+    /// by one of the previous instances of the server
+    pub fn existing(request: &RunRequest, id: &Uuid) -> V1Cgroups {
+        let mut cgroups =
+            UnifiedRepr::with_subsystems(PathBuf::from(id.to_string()), &subsystems(request));
+
+        cgroups.skip_create(&skipped_subsystems());
+
+        V1Cgroups { cgroups }
+    }
+}
+
+impl Cgroups for V1Cgroups {
+    fn apply_pre_exec(&self, cmd: &mut Command) {
+        if let Some(cgroup) = &self.cgroups.cpu() {
+            apply_cgroup_pre_exec(cmd, *cgroup);
+        }
+
+        if let Some(cgroup) = &self.cgroups.cpuacct() {
+            apply_cgroup_pre_exec(cmd, *cgroup);
+        }
+
+        if let Some(cgroup) = &self.cgroups.memory() {
+            apply_cgroup_pre_exec(cmd, *cgroup);
+        }
+
+        if let Some(cgroup) = &self.cgroups.blkio() {
+            apply_cgroup_pre_exec(cmd, *cgroup);
+        }
+
+        if let Some(cgroup) = &self.cgroups.cpuset() {
+            apply_cgroup_pre_exec(cmd, *cgroup);
+        }
+
+        if let Some(cgroup) = &self.cgroups.pids() {
+            apply_cgroup_pre_exec(cmd, *cgroup);
+        }
+
+        if let Some(cgroup) = &self.cgroups.freezer() {
+            apply_cgroup_pre_exec(cmd, *cgroup);
+        }
+    }
+
+    fn oom_killed(&self) -> bool {
+        match self.cgroups.memory().map(|memory| memory.oom_control()) {
+            Some(Ok(oom_control)) => oom_control.oom_kill.unwrap_or(0) > 0,
+            _ => false,
+        }
+    }
+
+    fn usage(&self) -> ResourceUsage {
+        let memory = self.cgroups.memory();
+        let (io_read_bytes, io_write_bytes) = io_service_bytes(self.cgroups.blkio());
+
+        ResourceUsage {
+            cpu_time: counter(self.cgroups.cpuacct(), "cpuacct.usage"),
+            memory: counter(memory, "memory.usage_in_bytes"),
+            peak_memory: counter(memory, "memory.max_usage_in_bytes"),
+            io_read_bytes,
+            io_write_bytes,
+            tasks: counter(self.cgroups.pids(), "pids.current"),
+        }
+    }
+
+    fn pids_limit_hits(&self) -> u64 {
+        match self.cgroups.pids().map(|pids| pids.events()) {
+            Some(Ok((_, count))) => count,
+            _ => 0,
+        }
+    }
+
+    fn procs(&self) -> Vec<u32> {
+        // all of the processes join the pids subsystem along with the others
+        match self.cgroups.pids() {
+            Some(pids) => read_procs(&pids.path().join("cgroup.procs")),
+            None => Vec::new(),
+        }
+    }
+
+    fn kill(&mut self) -> Result<()> {
+        // the frozen processes can't fork while being killed one by one,
+        // and they die once thawed
+        let frozen = self.freeze().is_ok();
+        let killed = kill_procs(&self.procs());
+
+        if frozen {
+            self.thaw()?;
+        }
+
+        killed
+    }
+
+    fn freeze(&mut self) -> Result<()> {
+        match self.cgroups.freezer_mut() {
+            Some(freezer) => Ok(freezer.freeze()?),
+            None => Err(anyhow!("The process isn't in the freezer subsystem")),
+        }
+    }
+
+    fn thaw(&mut self) -> Result<()> {
+        match self.cgroups.freezer_mut() {
+            Some(freezer) => Ok(freezer.thaw()?),
+            None => Err(anyhow!("The process isn't in the freezer subsystem")),
+        }
+    }
+
+    fn is_frozen(&self) -> bool {
+        matches!(
+            self.cgroups.freezer().map(|freezer| freezer.state()),
+            Some(Ok(State::Frozen)) | Some(Ok(State::Freezing))
+        )
+    }
+
+    fn delete(&mut self) -> Result<()> {
+        Ok(self.cgroups.delete()?)
+    }
+}
+
+/// Returns the kinds of control groups subsystems the process started with
+/// a given request is put into
+fn subsystems(request: &RunRequest) -> Vec<SubsystemKind> {
+    if !is_constrained(request) {
+        return Vec::new();
+    }
+
+    let mut subsystems = ACCOUNTED_SUBSYSTEMS.to_vec();
+    subsystems.push(SubsystemKind::Freezer);
+
+    if has_cpuset(request) {
+        subsystems.push(SubsystemKind::Cpuset);
+    }
+
+    subsystems
+}
+
+/// Returns the subsystems whose control groups are created along with the
+/// ones of the others sharing the hierarchy with them
+fn skipped_subsystems() -> Vec<SubsystemKind> {
+    match (fs::canonicalize(CPU_ROOT), fs::canonicalize(CPUACCT_ROOT)) {
+        (Ok(cpu), Ok(cpuacct)) if cpu == cpuacct => vec![SubsystemKind::Cpuacct],
+        _ => Vec::new(),
+    }
+}
+
+/// Reads a single number from a file of a given control group, 0 when
+/// there's no such group or it can't be read
+fn counter<C: Cgroup>(cgroup: Option<&C>, file: &str) -> u64 {
+    cgroup
+        .and_then(|cgroup| fs::read_to_string(cgroup.path().join(file)).ok())
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Sums up the bytes read from and written to all of the devices as
+/// accounted by the blkio throttling
+fn io_service_bytes<C: Cgroup>(cgroup: Option<&C>) -> (u64, u64) {
+    let stats = cgroup
+        .and_then(|cgroup| {
+            fs::read_to_string(cgroup.path().join("blkio.throttle.io_service_bytes")).ok()
+        })
+        .unwrap_or_default();
+
+    // made of "<major>:<minor> <operation> <bytes>" lines
+    stats.lines().fold((0, 0), |(read, write), line| {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+
+        match fields.as_slice() {
+            [_, "Read", bytes] => (read + bytes.parse().unwrap_or(0), write),
+            [_, "Write", bytes] => (read, write + bytes.parse().unwrap_or(0)),
+            _ => (read, write),
+        }
+    })
+}
+
+/// Returns the devices along with one of their limits, skipping the ones
+/// where it isn't set
+fn throttled(devices: &[DeviceLimits], limit: fn(&DeviceLimits) -> u64) -> Vec<(Device, u64)> {
+    devices
+        .iter()
+        .filter(|limits| limit(limits) > 0)
+        .map(|limits| (Device::from([limits.major, limits.minor]), limit(limits)))
+        .collect()
+}
+
+/// Returns the requested list of the CPUs or the memory nodes or the one of
+/// the root cpuset when none was requested
+fn inherited(requested: &str, file: &str) -> Result<IdSet> {
+    let list = if requested.is_empty() {
+        fs::read_to_string(Path::new(CPUSET_ROOT).join(file))
+            .with_context(|| format!("Couldn't read the root {}", file))?
+    } else {
+        requested.to_string()
+    };
+
+    list.trim()
+        .parse()
+        .with_context(|| format!("Invalid {}: {}", file, list.trim()))
+}
+
+fn apply_cgroup_pre_exec<C: Cgroup>(cmd: &mut Command, cgroup: &C) {
+    let path = cgroup.path().join("cgroup.procs");
+
+    unsafe {
+        cmd.pre_exec(move || std::fs::write(&path, std::process::id().to_string()));
+    }
+}
@@ -0,0 +1,284 @@
+use crate::runner::cgroups::{
+    cfs_quota_us, is_constrained, kill_procs, read_procs, Cgroups, DeviceLimits, CFS_PERIOD_US,
+    UNIFIED_ROOT,
+};
+use crate::runner::service::{run_request, ResourceUsage, RunRequest};
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::PathBuf;
+use tokio::process::Command;
+use uuid::Uuid;
+
+/// The range of cpu.shares of the v1 cpu subsystem that the max CPU value
+/// of the requests is expressed in
+const MIN_SHARES: u64 = 2;
+const MAX_SHARES: u64 = 262144;
+
+/// The range of cpu.weight of the v2 cpu controller
+const MIN_WEIGHT: u64 = 1;
+const MAX_WEIGHT: u64 = 10000;
+
+/// Controllers enabled for the constrained processes, whether or not they're
+/// constrained by them, for the sake of accounting their usage of the
+/// resources. The CPU time is accounted without enabling the cpu controller
+const ACCOUNTED_CONTROLLERS: [&str; 3] = ["memory", "io", "pids"];
+
+/// The control group of a process in the unified hierarchy. Only created
+/// when the process is constrained
+pub struct V2Cgroups {
+    path: Option<PathBuf>,
+}
+
+impl V2Cgroups {
+    pub fn create(request: &RunRequest, id: &Uuid, devices: &[DeviceLimits]) -> Result<V2Cgroups> {
+        if !is_constrained(request) {
+            return Ok(V2Cgroups { path: None });
+        }
+
+        let mut controllers = Vec::new();
+        let mut settings = Vec::new();
+
+        if let Some(run_request::Memory::MaxMemory(max)) = request.memory {
+            controllers.push("memory");
+            settings.push(("memory.max", max.to_string()));
+        }
+
+        if let Some(run_request::Cpu::MaxCpu(shares)) = request.cpu {
+            controllers.push("cpu");
+            settings.push(("cpu.weight", weight(shares).to_string()));
+        }
+
+        if let Some(run_request::CpuQuota::MaxCpus(cpus)) = request.cpu_quota {
+            controllers.push("cpu");
+            settings.push((
+                "cpu.max",
+                format!("{} {}", cfs_quota_us(cpus), CFS_PERIOD_US),
+            ));
+        }
+
+        // unlike in v1, the ones that weren't requested are inherited
+        // from the parent
+        if !request.cpuset_cpus.is_empty() {
+            controllers.push("cpuset");
+            settings.push(("cpuset.cpus", request.cpuset_cpus.clone()));
+        }
+
+        if !request.cpuset_mems.is_empty() {
+            controllers.push("cpuset");
+            settings.push(("cpuset.mems", request.cpuset_mems.clone()));
+        }
+
+        if let Some(run_request::Pids::MaxPids(max)) = request.pids {
+            controllers.push("pids");
+            settings.push(("pids.max", max.to_string()));
+        }
+
+        if !devices.is_empty() {
+            controllers.push("io");
+
+            // io.max takes the limits of a single device at a time
+            for limits in devices {
+                settings.push(("io.max", io_max(limits)));
+            }
+        }
+
+        controllers.extend_from_slice(&ACCOUNTED_CONTROLLERS);
+        controllers.sort_unstable();
+        controllers.dedup();
+
+        // the controllers have to be enabled for the children of the root
+        // for the control group to get them
+        for controller in controllers {
+            fs::write(
+                PathBuf::from(UNIFIED_ROOT).join("cgroup.subtree_control"),
+                format!("+{}", controller),
+            )
+            .with_context(|| format!("Couldn't enable the {} controller", controller))?;
+        }
+
+        let path = PathBuf::from(UNIFIED_ROOT).join(id.to_string());
+
+        fs::create_dir(&path)
+            .context("Couldn't create a Linux control group for the new process")?;
+
+        let mut cgroups = V2Cgroups { path: Some(path) };
+
+        if let Err(err) = cgroups.configure(&settings) {
+            cgroups.delete().ok();
+            return Err(err);
+        }
+
+        Ok(cgroups)
+    }
+
+    /// Represents the control group that was created for a given request
+    /// by one of the previous instances of the server
+    pub fn existing(request: &RunRequest, id: &Uuid) -> V2Cgroups {
+        V2Cgroups {
+            path: if is_constrained(request) {
+                Some(PathBuf::from(UNIFIED_ROOT).join(id.to_string()))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Returns the number kept in a given file, 0 when it can't be read
+    fn value(&self, file: &str) -> u64 {
+        match &self.path {
+            Some(path) => fs::read_to_string(path.join(file))
+                .ok()
+                .and_then(|value| value.trim().parse().ok())
+                .unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    /// Sums up the bytes read from and written to all of the devices
+    fn io_bytes(&self) -> (u64, u64) {
+        let stats = match &self.path {
+            Some(path) => fs::read_to_string(path.join("io.stat")).unwrap_or_default(),
+            None => return (0, 0),
+        };
+
+        // made of "<major>:<minor> rbytes=<bytes> wbytes=<bytes> ..." lines
+        stats
+            .split_whitespace()
+            .fold((0, 0), |(read, write), field| match field.split_once('=') {
+                Some(("rbytes", bytes)) => (read + bytes.parse().unwrap_or(0), write),
+                Some(("wbytes", bytes)) => (read, write + bytes.parse().unwrap_or(0)),
+                _ => (read, write),
+            })
+    }
+
+    /// Returns the count of a given event from one of the files made of
+    /// "<event> <count>" lines, 0 when it can't be read
+    fn event_count(&self, file: &str, event: &str) -> u64 {
+        let events = match &self.path {
+            Some(path) => fs::read_to_string(path.join(file)).unwrap_or_default(),
+            None => return 0,
+        };
+
+        events
+            .lines()
+            .find_map(|line| {
+                let mut fields = line.split_whitespace();
+
+                if fields.next() == Some(event) {
+                    fields.next().and_then(|count| count.parse().ok())
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(0)
+    }
+
+    fn configure(&self, settings: &[(&str, String)]) -> Result<()> {
+        if let Some(path) = &self.path {
+            for (file, value) in settings {
+                fs::write(path.join(file), value)
+                    .with_context(|| format!("Couldn't set {} to {}", file, value))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Cgroups for V2Cgroups {
+    fn apply_pre_exec(&self, cmd: &mut Command) {
+        if let Some(path) = &self.path {
+            let path = path.join("cgroup.procs");
+
+            unsafe {
+                cmd.pre_exec(move || std::fs::write(&path, std::process::id().to_string()));
+            }
+        }
+    }
+
+    fn oom_killed(&self) -> bool {
+        self.event_count("memory.events", "oom_kill") > 0
+    }
+
+    fn usage(&self) -> ResourceUsage {
+        let (io_read_bytes, io_write_bytes) = self.io_bytes();
+
+        ResourceUsage {
+            cpu_time: self.event_count("cpu.stat", "usage_usec") * 1000,
+            memory: self.value("memory.current"),
+            // memory.peak is only there since Linux 5.19
+            peak_memory: self.value("memory.peak"),
+            io_read_bytes,
+            io_write_bytes,
+            tasks: self.value("pids.current"),
+        }
+    }
+
+    fn pids_limit_hits(&self) -> u64 {
+        self.event_count("pids.events", "max")
+    }
+
+    fn procs(&self) -> Vec<u32> {
+        match &self.path {
+            Some(path) => read_procs(&path.join("cgroup.procs")),
+            None => Vec::new(),
+        }
+    }
+
+    fn kill(&mut self) -> Result<()> {
+        match &self.path {
+            // cgroup.kill is only there since Linux 5.14
+            Some(path) if path.join("cgroup.kill").exists() => {
+                self.configure(&[("cgroup.kill", "1".to_string())])
+            }
+            _ => kill_procs(&self.procs()),
+        }
+    }
+
+    fn freeze(&mut self) -> Result<()> {
+        self.configure(&[("cgroup.freeze", "1".to_string())])
+    }
+
+    fn thaw(&mut self) -> Result<()> {
+        self.configure(&[("cgroup.freeze", "0".to_string())])
+    }
+
+    fn is_frozen(&self) -> bool {
+        // the requested state, the actual one being the "frozen" entry of
+        // cgroup.events
+        self.value("cgroup.freeze") == 1
+    }
+
+    fn delete(&mut self) -> Result<()> {
+        if let Some(path) = &self.path {
+            fs::remove_dir(path).context("Couldn't delete the control group")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats the limits of a device as a line of io.max, leaving out the ones
+/// that aren't set
+fn io_max(limits: &DeviceLimits) -> String {
+    let keys = [
+        ("rbps", limits.read_bps),
+        ("wbps", limits.write_bps),
+        ("riops", limits.read_iops),
+        ("wiops", limits.write_iops),
+    ];
+
+    keys.iter().filter(|(_, limit)| *limit > 0).fold(
+        format!("{}:{}", limits.major, limits.minor),
+        |line, (key, limit)| format!("{} {}={}", line, key, limit),
+    )
+}
+
+/// Converts the v1 cpu.shares into the v2 cpu.weight, mapping one range
+/// onto the other the same way the container runtimes do
+fn weight(shares: u64) -> u64 {
+    let shares = shares.clamp(MIN_SHARES, MAX_SHARES);
+
+    MIN_WEIGHT + (shares - MIN_SHARES) * (MAX_WEIGHT - MIN_WEIGHT) / (MAX_SHARES - MIN_SHARES)
+}
@@ -0,0 +1,63 @@
+use crate::runner::pty;
+use crate::runner::service::TerminalSize;
+use anyhow::{Context, Result};
+use log::warn;
+use std::os::unix::io::AsRawFd;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+/// The number of chunks of the input waiting to be written into the stdin
+/// of a process before the writers have to wait
+pub const INPUT_CAPACITY: usize = 16;
+
+/// What the attached clients send to a process
+#[derive(Debug)]
+pub enum Input {
+    Data(Vec<u8>),
+
+    /// the new size of the pseudo-terminal of the process
+    Resize(TerminalSize),
+}
+
+/// The stdin of a process: either a pipe or the master side of its
+/// pseudo-terminal
+pub trait Stdin: AsyncWrite + AsRawFd + Unpin + Send {}
+
+impl<T: AsyncWrite + AsRawFd + Unpin + Send> Stdin for T {}
+
+/// Writes the given bytes into the stdin of a process, followed by the chunks
+/// of the input received until all of the senders are dropped. The pipe gets
+/// closed afterwards, making the process read the end of the file
+pub async fn feed(
+    mut stdin: Box<dyn Stdin>,
+    initial: Vec<u8>,
+    mut input: Option<mpsc::Receiver<Input>>,
+) -> Result<()> {
+    write(&mut stdin, &initial).await?;
+
+    if let Some(input) = &mut input {
+        while let Some(input) = input.recv().await {
+            match input {
+                Input::Data(chunk) => write(&mut stdin, &chunk).await?,
+                Input::Resize(size) => {
+                    if let Err(err) = pty::resize(stdin.as_raw_fd(), &size) {
+                        warn!("{}", err);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn write(stdin: &mut Box<dyn Stdin>, bytes: &[u8]) -> Result<()> {
+    if bytes.is_empty() {
+        return Ok(());
+    }
+
+    stdin
+        .write_all(bytes)
+        .await
+        .context("Couldn't write into the STDIN pipe")
+}
@@ -0,0 +1,380 @@
+// a started entry outweighs the others by far, but prost can't box it
+#![allow(clippy::large_enum_variant)]
+
+tonic::include_proto!("journal");
+
+use crate::runner::cgroups::Accounting;
+use crate::runner::output::Segments;
+use crate::runner::process_map::{Process, ProcessStatus, Termination};
+use crate::runner::procfs;
+use crate::runner::service::{ResourceUsage, RunRequest};
+use anyhow::{Context, Result};
+use log::warn;
+use prost::Message;
+use std::collections::HashMap;
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process::ExitStatus;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Append-only on-disk journal of the jobs started by the Runner. Replaying
+/// it allows to rebuild the map of processes after the server restarts
+#[derive(Debug)]
+pub struct Journal {
+    file: Mutex<File>,
+}
+
+impl Journal {
+    /// Opens the journal at a given path, creating it if it doesn't exist yet.
+    /// Returns the journal along with the processes replayed from it
+    pub async fn open(path: &Path) -> Result<(Journal, HashMap<Uuid, Process>)> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .await
+            .context("Couldn't open the jobs journal")?;
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)
+            .await
+            .context("Couldn't read the jobs journal")?;
+
+        let (processes, valid, deleted) = replay(&data, procfs::boot_id())?;
+
+        if deleted > 0 {
+            // the entries of the deleted processes would pile up otherwise
+            let file = compact(path, &processes).await?;
+
+            return Ok((
+                Journal {
+                    file: Mutex::new(file),
+                },
+                processes,
+            ));
+        }
+
+        if valid < data.len() {
+            // the previous instance most probably went down in the middle of
+            // appending an entry - let's drop it not to corrupt the entries
+            // appended from now on
+
+            warn!(
+                "Discarding {} bytes of a partially written entry in the jobs journal",
+                data.len() - valid
+            );
+
+            file.set_len(valid as u64)
+                .await
+                .context("Couldn't truncate the jobs journal")?;
+        }
+
+        Ok((
+            Journal {
+                file: Mutex::new(file),
+            },
+            processes,
+        ))
+    }
+
+    /// Records the start of a process
+    pub async fn started(&self, id: &Uuid, process: &Process) -> Result<()> {
+        self.append(started(id, process)).await
+    }
+
+    /// Records the exit status of a finished process along with its accounting
+    /// and the reason the server stopped it for, if it did
+    pub async fn finished(
+        &self,
+        id: &Uuid,
+        exit_status: ExitStatus,
+        accounting: &Accounting,
+        termination: Option<Termination>,
+    ) -> Result<()> {
+        self.append(Entry {
+            id: id.to_string(),
+            event: Some(entry::Event::Finished(finished(
+                SystemTime::now(),
+                exit_status,
+                accounting,
+                termination,
+            ))),
+        })
+        .await
+    }
+
+    /// Records that a process exited without the server being able to get
+    /// its exit status
+    pub async fn lost(&self, id: &Uuid) -> Result<()> {
+        self.append(Entry {
+            id: id.to_string(),
+            event: Some(entry::Event::Lost(Lost {
+                finished_at: to_millis(SystemTime::now()),
+            })),
+        })
+        .await
+    }
+
+    /// Records that a process got deleted along with its logs
+    pub async fn deleted(&self, id: &Uuid) -> Result<()> {
+        self.append(Entry {
+            id: id.to_string(),
+            event: Some(entry::Event::Deleted(Deleted {
+                deleted_at: to_millis(SystemTime::now()),
+            })),
+        })
+        .await
+    }
+
+    async fn append(&self, entry: Entry) -> Result<()> {
+        let mut buffer = Vec::with_capacity(entry.encoded_len() + 10);
+        entry.encode_length_delimited(&mut buffer)?;
+
+        let mut file = self.file.lock().await;
+
+        file.write_all(&buffer)
+            .await
+            .context("Couldn't append to the jobs journal")?;
+        file.sync_data()
+            .await
+            .context("Couldn't sync the jobs journal")?;
+
+        Ok(())
+    }
+}
+
+/// Creates the record of the exit of a process along with the totals of its
+/// resource usage
+fn finished(
+    finished_at: SystemTime,
+    exit_status: ExitStatus,
+    accounting: &Accounting,
+    termination: Option<Termination>,
+) -> Finished {
+    Finished {
+        finished_at: to_millis(finished_at),
+        exit_status: exit_status.into_raw(),
+        pids_limit_hits: accounting.pids_limit_hits,
+        oom_killed: accounting.oom_killed,
+        peak_memory: accounting.usage.peak_memory,
+        cpu_time: accounting.usage.cpu_time,
+        io_read_bytes: accounting.usage.io_read_bytes,
+        io_write_bytes: accounting.usage.io_write_bytes,
+        timed_out: termination == Some(Termination::TimedOut),
+        log_quota_exceeded: termination == Some(Termination::LogQuotaExceeded),
+    }
+}
+
+/// Creates the entry recording the start of a process. The environment and
+/// the input of the process aren't needed to replay it and can hold secrets,
+/// so they're left out of the journal
+fn started(id: &Uuid, process: &Process) -> Entry {
+    Entry {
+        id: id.to_string(),
+        event: Some(entry::Event::Started(Started {
+            request: Some(RunRequest {
+                env: HashMap::new(),
+                stdin: Vec::new(),
+                ..process.request.clone()
+            }),
+            pid: process.pid,
+            started_at: to_millis(process.started_at),
+            start_time: process.start_time.unwrap_or(0),
+            boot_id: procfs::boot_id().unwrap_or_default(),
+        })),
+    }
+}
+
+/// Rewrites the journal at a given path with only the entries needed to
+/// replay given processes. The new journal replaces the old one at once, so
+/// that a crash in the middle leaves either of them intact
+async fn compact(path: &Path, processes: &HashMap<Uuid, Process>) -> Result<File> {
+    let mut ordered = processes.iter().collect::<Vec<_>>();
+    ordered.sort_by_key(|(_, process)| process.started_at);
+
+    let mut buffer = Vec::new();
+
+    for (id, process) in ordered {
+        let finish = match process.status {
+            ProcessStatus::Running | ProcessStatus::Paused => None,
+            ProcessStatus::Stopped(exit_status) => Some(entry::Event::Finished(finished(
+                process.finished_at.unwrap_or_else(SystemTime::now),
+                exit_status,
+                &process.accounting,
+                process.termination,
+            ))),
+            ProcessStatus::Lost => Some(entry::Event::Lost(Lost {
+                finished_at: to_millis(process.finished_at.unwrap_or_else(SystemTime::now)),
+            })),
+        };
+
+        started(id, process).encode_length_delimited(&mut buffer)?;
+
+        if let Some(event) = finish {
+            Entry {
+                id: id.to_string(),
+                event: Some(event),
+            }
+            .encode_length_delimited(&mut buffer)?;
+        }
+    }
+
+    let compacted = path.with_extension("compacted");
+    let mut file = File::create(&compacted)
+        .await
+        .context("Couldn't create the compacted jobs journal")?;
+
+    file.write_all(&buffer)
+        .await
+        .context("Couldn't write the compacted jobs journal")?;
+    file.sync_all()
+        .await
+        .context("Couldn't sync the compacted jobs journal")?;
+
+    tokio::fs::rename(&compacted, path)
+        .await
+        .context("Couldn't replace the jobs journal with the compacted one")?;
+
+    OpenOptions::new()
+        .append(true)
+        .open(path)
+        .await
+        .context("Couldn't open the jobs journal")
+}
+
+/// Replays the journal entries into a map of processes. Returns the map,
+/// the number of bytes that were decoded successfully and the number of
+/// the processes deleted since they were started. Only the last entry can
+/// be cut short, an entry that can't be decoded anywhere else is an error
+fn replay(data: &[u8], boot_id: Option<String>) -> Result<(HashMap<Uuid, Process>, usize, usize)> {
+    let mut processes = HashMap::new();
+    let mut deleted = 0;
+    let mut rest = data;
+
+    while !rest.is_empty() {
+        let mut cursor = rest;
+        let offset = data.len() - rest.len();
+
+        let length = match prost::decode_length_delimiter(&mut cursor) {
+            Ok(length) if length <= cursor.len() => length,
+            // going past the end of the file
+            Ok(_) => break,
+            Err(_) if is_cut_short(rest) => break,
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("The jobs journal is corrupted at byte {}", offset))
+            }
+        };
+
+        let entry = Entry::decode(&cursor[..length])
+            .with_context(|| format!("The jobs journal is corrupted at byte {}", offset))?;
+
+        rest = &cursor[length..];
+
+        let id = match Uuid::parse_str(&entry.id) {
+            Ok(id) => id,
+            Err(_) => {
+                warn!("Skipping a journal entry with invalid id: {}", &entry.id);
+                continue;
+            }
+        };
+
+        match entry.event {
+            Some(entry::Event::Started(started)) => {
+                // the start time is only meaningful within the same boot
+                let start_time = if started.start_time > 0
+                    && boot_id.as_deref() == Some(started.boot_id.as_str())
+                {
+                    Some(started.start_time)
+                } else {
+                    None
+                };
+
+                processes.insert(
+                    id,
+                    Process {
+                        pid: started.pid,
+                        start_time,
+                        status: ProcessStatus::Running,
+                        request: started.request.unwrap_or_default(),
+                        started_at: from_millis(started.started_at),
+                        finished_at: None,
+                        accounting: Accounting::default(),
+                        termination: None,
+                        output: None,
+                        segments: Segments::default(),
+                        stdin: None,
+                    },
+                );
+            }
+            Some(entry::Event::Finished(finished)) => {
+                if let Some(process) = processes.get_mut(&id) {
+                    process.status =
+                        ProcessStatus::Stopped(ExitStatus::from_raw(finished.exit_status));
+                    process.finished_at = Some(from_millis(finished.finished_at));
+                    process.accounting = Accounting {
+                        oom_killed: finished.oom_killed,
+                        pids_limit_hits: finished.pids_limit_hits,
+                        usage: ResourceUsage {
+                            cpu_time: finished.cpu_time,
+                            peak_memory: finished.peak_memory,
+                            io_read_bytes: finished.io_read_bytes,
+                            io_write_bytes: finished.io_write_bytes,
+                            ..Default::default()
+                        },
+                    };
+                    process.termination = if finished.timed_out {
+                        Some(Termination::TimedOut)
+                    } else if finished.log_quota_exceeded {
+                        Some(Termination::LogQuotaExceeded)
+                    } else {
+                        None
+                    };
+                } else {
+                    warn!("Skipping a journal entry for unknown process: {}", &id);
+                }
+            }
+            Some(entry::Event::Lost(lost)) => {
+                if let Some(process) = processes.get_mut(&id) {
+                    process.status = ProcessStatus::Lost;
+                    process.finished_at = Some(from_millis(lost.finished_at));
+                } else {
+                    warn!("Skipping a journal entry for unknown process: {}", &id);
+                }
+            }
+            Some(entry::Event::Deleted(_)) => {
+                if processes.remove(&id).is_some() {
+                    deleted += 1;
+                } else {
+                    warn!("Skipping a journal entry for unknown process: {}", &id);
+                }
+            }
+            None => {
+                warn!("Skipping an empty journal entry for: {}", &id);
+            }
+        }
+    }
+
+    Ok((processes, data.len() - rest.len(), deleted))
+}
+
+/// Tells if the bytes at the end of the journal are the beginning of the
+/// length of an entry, every byte of which tells that more of it follows
+fn is_cut_short(rest: &[u8]) -> bool {
+    rest.len() < 10 && rest.iter().all(|byte| byte & 0x80 != 0)
+}
+
+pub fn to_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn from_millis(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
@@ -3,45 +3,263 @@ pub mod service;
 pub mod server;
 
 mod cgroups;
+mod input;
+mod journal;
+mod output;
 mod process_map;
+mod procfs;
+mod pty;
+mod retention;
+mod users;
 
 use anyhow::{anyhow, Context, Result};
-use cgroups::{apply_cgroup_pre_exec, create_cgroups};
-use futures::stream::{unfold, Stream};
+use cgroups::{
+    create_cgroups, device_limits, existing_cgroups, is_constrained, is_valid_cpu_quota,
+    is_valid_cpuset, Accounting, Cgroups,
+};
+use futures::stream::{self, unfold, Stream, StreamExt};
+use input::{Input, Stdin};
+use journal::{to_millis, Journal};
 use log::{info, warn};
 use nix::errno::Errno;
 use nix::sys::signal;
-use nix::unistd::Pid;
+use nix::unistd::{setsid, Pid};
+pub use output::Quotas;
+use output::{Limits, LogPaths, Reader, Record, Segment, Segments, Usage, Writer};
 use process_map::{
-    ProcessMap,
-    ProcessStatus::{Running, Stopped},
+    Process, ProcessMap,
+    ProcessStatus::{Lost, Paused, Running, Stopped},
+    Termination,
 };
+use pty::Pty;
+pub use retention::Retention;
 use service::{
+    attach_response::{self, attach_error, AttachError},
+    delete_response::{delete_error, DeleteError},
+    list_request,
+    list_response::{self, list_error, Job, ListError},
     log_request,
-    log_response::{log_error, LogError},
+    log_response::{log_error, Chunk, LogError},
+    pause_response::{pause_error, PauseError},
+    resume_response::{resume_error, ResumeError},
+    run_follow_response, run_request,
     run_response::{run_error, RunError},
+    signal_response::{signal_error, SignalError},
+    stats_response::{stats_error, StatsError},
     status_response::{status_error, status_result, StatusError, StatusResult},
+    stop_request,
     stop_response::{stop_error, StopError},
-    InternalError, LogRequest, RunRequest, StatusRequest, StopRequest,
+    wait_request,
+    wait_response::{wait_error, WaitError},
+    watch_response::{event::Kind, watch_error, Event, WatchError},
+    AttachRequest, DeleteRequest, InternalError, ListRequest, LogRequest, PauseRequest,
+    ResourceUsage, ResumeRequest, RunRequest, SignalRequest, StatsRequest, StatusRequest,
+    StopRequest, WaitRequest, WatchRequest,
 };
-use std::fs::File;
+use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::os::unix::process::ExitStatusExt;
-use std::path::PathBuf;
+use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tokio::io::AsyncReadExt;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::AsyncRead;
 use tokio::process::Command;
+use tokio::sync::{broadcast, mpsc, watch};
+pub use users::UsersPolicy;
 use uuid::Uuid;
 
+/// The number of processes listed at once unless requested otherwise
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// The maximum number of processes listed at once
+const MAX_PAGE_SIZE: usize = 1000;
+
+/// The number of lifecycle events kept for watchers lagging behind
+const EVENTS_CAPACITY: usize = 1024;
+
+/// How often the resource usage is sampled for the followers unless
+/// requested otherwise
+const DEFAULT_STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the stopped processes are given to exit before getting killed
+/// unless requested otherwise
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Where the output of a process gets captured from: one of its pipes or the
+/// master side of its pseudo-terminal
+type OutputSource = Box<dyn AsyncRead + Unpin + Send>;
+
 /// State of the log stream
 struct StreamState {
-    processes: ProcessMap,
-    file: tokio::fs::File,
-    buffer: Vec<u8>,
-    id: Uuid,
+    /// notifies about the output captured until the capture is over
+    output: Option<watch::Receiver<u64>>,
+
+    reader: Reader,
+
+    /// the requested descriptor
+    descriptor: log_request::Descriptor,
+
+    /// the record read but not streamed yet along with its sequence number
+    pending: Option<(u64, Record)>,
+
+    /// the byte offsets in the output of each of the descriptors to start
+    /// streaming from
+    stdout_start: u64,
+    stderr_start: u64,
+
+    buffer_size: usize,
     close: bool,
 }
 
+impl StreamState {
+    /// Returns the next chunk of the requested output or None if none was
+    /// captured yet. Consecutive records of the same descriptor are joined
+    /// as long as they fit in the buffer
+    async fn next_chunk(&mut self) -> Result<Option<Chunk>> {
+        let mut chunk: Option<Chunk> = None;
+
+        loop {
+            let (sequence, mut record) = match self.pending.take() {
+                Some(pending) => pending,
+                None => match self.reader.next().await? {
+                    Some(record) => record,
+                    None => break,
+                },
+            };
+
+            let start = self.start(record.descriptor);
+
+            if !self.wants(record.descriptor) || record.offset + record.length as u64 <= start {
+                continue;
+            }
+
+            // the start can fall in the middle of a record:
+            if record.offset < start {
+                record.length -= (start - record.offset) as u32;
+                record.offset = start;
+            }
+
+            match &mut chunk {
+                Some(chunk)
+                    if chunk.descriptor == record.descriptor as i32
+                        && chunk.data.len() + record.length as usize <= self.buffer_size =>
+                {
+                    let data = self.reader.read(&record).await?;
+                    chunk.data.extend(data);
+                }
+                Some(_) => {
+                    self.pending = Some((sequence, record));
+                    break;
+                }
+                None => {
+                    chunk = Some(Chunk {
+                        descriptor: record.descriptor as i32,
+                        data: self.reader.read(&record).await?,
+                        sequence,
+                        time: record.time,
+                        offset: record.offset,
+                    });
+                }
+            }
+        }
+
+        Ok(chunk)
+    }
+
+    /// Moves the start of the stream so that it begins with the given number
+    /// of the last lines of the output captured so far. The output is streamed
+    /// from the beginning when it has fewer lines
+    async fn tail(&mut self, paths: &LogPaths, lines: u32) -> Result<()> {
+        let mut remaining = lines;
+        let mut last = true;
+
+        // walking the segments backwards, starting with the newest one
+        for number in paths.segments().into_iter().rev() {
+            let mut segment = match Segment::open(paths, number).await {
+                Ok(segment) => segment,
+                // removed in the meantime along with the older ones
+                Err(_) => break,
+            };
+
+            let mut records = Vec::new();
+
+            while let Some((_, record)) = segment.next().await? {
+                if self.wants(record.descriptor) {
+                    records.push(record);
+                }
+            }
+
+            for position in (0..records.len()).rev() {
+                let record = records[position];
+                let data = segment.read(&record).await?;
+
+                for i in (0..data.len()).rev() {
+                    // the newline ending the output doesn't begin another line
+                    if data[i] == b'\n' && !last {
+                        remaining -= 1;
+
+                        if remaining == 0 {
+                            let (descriptor, other) = match record.descriptor {
+                                log_request::Descriptor::Stderr => (
+                                    log_request::Descriptor::Stderr,
+                                    log_request::Descriptor::Stdout,
+                                ),
+                                _ => (
+                                    log_request::Descriptor::Stdout,
+                                    log_request::Descriptor::Stderr,
+                                ),
+                            };
+
+                            // the other descriptor starts right after its output
+                            // preceding the line
+                            let other_start = match records[..position]
+                                .iter()
+                                .rev()
+                                .find(|previous| previous.descriptor == other)
+                            {
+                                Some(previous) => previous.offset + previous.length as u64,
+                                None => segment.base(other).await?.unwrap_or(0),
+                            };
+
+                            self.set_start(descriptor, record.offset + i as u64 + 1);
+                            self.set_start(other, other_start);
+
+                            return Ok(());
+                        }
+                    }
+
+                    last = false;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn wants(&self, descriptor: log_request::Descriptor) -> bool {
+        match self.descriptor {
+            log_request::Descriptor::Both => true,
+            requested => requested == descriptor,
+        }
+    }
+
+    fn start(&self, descriptor: log_request::Descriptor) -> u64 {
+        match descriptor {
+            log_request::Descriptor::Stderr => self.stderr_start,
+            _ => self.stdout_start,
+        }
+    }
+
+    fn set_start(&mut self, descriptor: log_request::Descriptor, offset: u64) {
+        match descriptor {
+            log_request::Descriptor::Stderr => self.stderr_start = offset,
+            _ => self.stdout_start = offset,
+        }
+    }
+}
+
 /// Processes runner struct. Includes processes states and allows to
 /// run them, stop, get their status and the stream of logs
 #[derive(Clone, Debug)]
@@ -54,6 +272,21 @@ pub struct Runner {
 
     /// The size of the buffer for streaming logs
     buffer_size: Option<usize>,
+
+    /// where to persist the processes map between restarts
+    journal: Option<Arc<Journal>>,
+
+    /// lifecycle events of the processes
+    events: broadcast::Sender<Event>,
+
+    /// limits of the disk space taken by the logs
+    quotas: Quotas,
+
+    /// the disk space taken by the logs
+    log_usage: Usage,
+
+    /// when to delete the finished processes along with their logs
+    retention: Retention,
 }
 
 // A more real implementation would make sure that the log_dir exists
@@ -64,11 +297,179 @@ impl Default for Runner {
             processes: ProcessMap::default(),
             log_dir: "tmp".to_string(),
             buffer_size: Some(256),
+            journal: None,
+            events: broadcast::channel(EVENTS_CAPACITY).0,
+            quotas: Quotas::default(),
+            log_usage: Usage::default(),
+            retention: Retention::default(),
         }
     }
 }
 
 impl Runner {
+    /// Creates a Runner that persists its processes map in the journal under
+    /// a given path. Processes recorded by previous instances are loaded back
+    /// so that their status and logs remain available. The logs are kept
+    /// within given quotas and the finished processes get deleted according
+    /// to the retention policies.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of the Tokio runtime.
+    // Allow dead code as the client binary doesn't use it
+    #[allow(dead_code)]
+    pub async fn open(
+        log_dir: String,
+        journal_path: &Path,
+        quotas: Quotas,
+        retention: Retention,
+    ) -> Result<Runner> {
+        let (journal, mut processes) = Journal::open(journal_path).await?;
+        let mut adopted = Vec::new();
+
+        // The children of the previous instance got reparented so there's
+        // no way of waiting for them. The ones still alive are watched over
+        // until they exit. Others are marked as lost.
+
+        for (id, process) in processes.iter_mut() {
+            if let Running = process.status {
+                let alive = match process.start_time {
+                    Some(start_time) => {
+                        procfs::is_alive(process.pid, start_time)
+                            && (!is_constrained(&process.request)
+                                || procfs::is_in_cgroup(process.pid, id))
+                    }
+                    None => false,
+                };
+
+                if alive {
+                    // paused by the previous instance
+                    if is_constrained(&process.request)
+                        && existing_cgroups(&process.request, id).is_frozen()
+                    {
+                        process.status = Paused;
+                    }
+
+                    info!("Adopting process {} ({})", &id, &process.pid);
+                    adopted.push((*id, process.clone()));
+                } else {
+                    warn!("Process {} exited while the server was down", &id);
+
+                    if let Err(err) = journal.lost(id).await {
+                        warn!("Couldn't record the loss of {}: {}", &id, err);
+                    }
+
+                    process.status = Lost;
+                    process.finished_at = Some(SystemTime::now());
+                }
+            }
+        }
+
+        info!(
+            "Loaded {} processes from {}",
+            processes.len(),
+            journal_path.display()
+        );
+
+        let ids = processes.keys().cloned().collect();
+        let (log_usage, mut segments) = output::scan(&log_dir, &ids).await?;
+
+        for (id, process) in processes.iter_mut() {
+            if let Some(segments) = segments.remove(id) {
+                process.segments = segments;
+            }
+        }
+
+        let runner = Runner {
+            processes: Arc::new(tokio::sync::RwLock::new(processes)),
+            log_dir,
+            journal: Some(Arc::new(journal)),
+            quotas,
+            log_usage: Arc::new(AtomicU64::new(log_usage)),
+            retention,
+            ..Default::default()
+        };
+
+        for (id, process) in adopted {
+            let cgroups = existing_cgroups(&process.request, &id);
+
+            // checked to be Some above
+            runner.adopt(id, process.pid, process.start_time.unwrap(), cgroups);
+
+            // the time it ran for while the server was down counts too
+            if let Some(run_request::Timeout::TimeoutSeconds(seconds)) = process.request.timeout {
+                let elapsed = process.started_at.elapsed().unwrap_or_default();
+                runner.enforce_timeout(id, Duration::from_secs(seconds).saturating_sub(elapsed));
+            }
+        }
+
+        if retention.is_enabled() {
+            let collector = runner.clone();
+
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(retention.interval);
+
+                loop {
+                    interval.tick().await;
+                    collector.collect().await;
+                }
+            });
+        }
+
+        Ok(runner)
+    }
+
+    /// Watches over a process left running by one of the previous instances
+    /// of the server. As it's not our child, it can't be waited for and its
+    /// existence is polled for instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of the Tokio runtime.
+    fn adopt(&self, id: Uuid, pid: u32, start_time: u64, mut cgroups: Box<dyn Cgroups>) {
+        let processes = Arc::clone(&self.processes);
+        let journal = self.journal.clone();
+        let events = self.events.clone();
+
+        tokio::spawn(async move {
+            while procfs::is_alive(pid, start_time) {
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
+
+            info!("Adopted process {} ({}) exited", &id, &pid);
+
+            if let Some(journal) = &journal {
+                if let Err(err) = journal.lost(&id).await {
+                    warn!("Couldn't record the loss of {}: {}", &id, err);
+                }
+            }
+
+            let mut map = processes.write().await;
+
+            if let Some(process) = (*map).get_mut(&id) {
+                process.status = Lost;
+                process.finished_at = Some(SystemTime::now());
+            }
+
+            drop(map);
+
+            // it's fine for no one to be watching:
+            events.send(event(&id, Kind::Lost)).ok();
+
+            while !cgroups.procs().is_empty() {
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
+
+            if let Err(err) = cgroups.delete() {
+                warn!(
+                    "Couldn't delete control group for {}: {}",
+                    &id,
+                    err.to_string()
+                )
+            }
+        });
+    }
+
     /// Executes given command. Allows to specify the command name, arguments and
     /// resource constraints. Returns a UUID of the process or an error.
     ///
@@ -80,61 +481,307 @@ impl Runner {
             return Err(run_error::Error::NameEmptyError.into());
         }
 
+        if !is_valid_cpu_quota(request) {
+            return Err(run_error::Error::InvalidCpuQuotaError.into());
+        }
+
+        if !is_valid_cpuset(request) {
+            return Err(run_error::Error::InvalidCpusetError.into());
+        }
+
+        if let Some(run_request::Timeout::TimeoutSeconds(0)) = request.timeout {
+            return Err(run_error::Error::InvalidTimeoutError.into());
+        }
+
+        if !request.working_dir.is_empty() && !Path::new(&request.working_dir).is_dir() {
+            return Err(RunError::with_details(
+                run_error::Error::InvalidWorkingDirError,
+                &request.working_dir,
+            ));
+        }
+
+        if let Some((key, _)) = request
+            .env
+            .iter()
+            .find(|(key, value)| !is_valid_env(key, value))
+        {
+            return Err(RunError::with_details(
+                run_error::Error::InvalidEnvError,
+                key,
+            ));
+        }
+
+        let credentials = users::credentials(request)?;
+        let devices = device_limits(request)?;
+        let pty = if request.tty {
+            Some(Pty::open(request.terminal_size.as_ref())?)
+        } else {
+            None
+        };
+
         let id = Uuid::new_v4();
-        let mut cgroups = create_cgroups(request, &id).context("Couldn't create a cgroup")?;
-        let stdout =
-            File::create(self.stdout_path(&id)).context("Couldn't open log file for STDOUT")?;
-        let stderr =
-            File::create(self.stderr_path(&id)).context("Couldn't open log file for STDERR")?;
+        let mut cgroups =
+            create_cgroups(request, &id, &devices).context("Couldn't create a cgroup")?;
+
+        let policy = match run_request::LogPolicy::from_i32(request.log_policy) {
+            Some(policy) => policy,
+            None => return Err(InternalError {
+                description:
+                    "Given log policy is invalid. Are you using compatible version of the client?"
+                        .to_string(),
+            }
+            .into()),
+        };
+
+        let limits = Limits {
+            quota: match request.log_quota {
+                Some(run_request::LogQuota::MaxLogSize(quota)) => Some(quota),
+                None => self.quotas.job,
+            },
+            policy,
+            segments: request.log_segments,
+            total: self.quotas.total,
+        };
+
+        let segments = Segments::default();
+
+        let writer = Writer::create(
+            LogPaths::new(&self.log_dir, &id, &segments),
+            limits,
+            Arc::clone(&self.log_usage),
+        )
+        .await?;
 
         let mut cmd = Command::new(&request.command);
 
         cmd.args(&request.arguments);
-        cmd.stdout(stdout);
-        cmd.stderr(stderr);
 
-        if let Some(cgroup) = &cgroups.cpu() {
-            apply_cgroup_pre_exec(&mut cmd, *cgroup);
+        if request.clear_env {
+            cmd.env_clear();
+        }
+
+        if let Some(user) = &credentials.user {
+            cmd.env("HOME", &user.dir);
+            cmd.env("USER", &user.name);
+            cmd.env("LOGNAME", &user.name);
+        }
+
+        cmd.envs(&request.env);
+
+        if !request.working_dir.is_empty() {
+            cmd.current_dir(&request.working_dir);
         }
 
-        if let Some(cgroup) = &cgroups.memory() {
-            apply_cgroup_pre_exec(&mut cmd, *cgroup);
+        if pty.is_none() {
+            cmd.stdin(if request.stdin.is_empty() && !request.open_stdin {
+                Stdio::null()
+            } else {
+                Stdio::piped()
+            });
+            cmd.stdout(Stdio::piped());
+            cmd.stderr(Stdio::piped());
+        }
+
+        cgroups.apply_pre_exec(&mut cmd);
+
+        // runs in a session and a process group of its own, so that the
+        // signals can reach all of its descendants
+        unsafe {
+            cmd.pre_exec(|| {
+                setsid()
+                    .map(drop)
+                    .map_err(|_| std::io::Error::last_os_error())
+            });
         }
 
-        if let Some(cgroup) = &cgroups.blkio() {
-            apply_cgroup_pre_exec(&mut cmd, *cgroup);
+        // becomes the controlling terminal of the new session
+        if let Some(pty) = &pty {
+            pty.apply(&mut cmd)?;
         }
 
+        let master = pty.map(Pty::into_master).transpose()?;
+
+        credentials.apply_pre_exec(&mut cmd);
+
         let spawn = cmd.spawn();
 
         match spawn {
             Ok(mut child) => {
                 let sys_pid: u32 = child.id().unwrap();
+                let (output_sender, output_receiver) = watch::channel(0);
                 let processes = Arc::clone(&self.processes);
+                let journal = self.journal.clone();
+                let events = self.events.clone();
+                let (input_sender, input_receiver) = if request.open_stdin || request.tty {
+                    let (sender, receiver) = mpsc::channel(input::INPUT_CAPACITY);
+                    (Some(sender), Some(receiver))
+                } else {
+                    (None, None)
+                };
+
+                let process = Process {
+                    pid: sys_pid,
+                    start_time: procfs::start_time(sys_pid),
+                    status: Running,
+                    // the input isn't kept along with the request
+                    request: RunRequest {
+                        stdin: Vec::new(),
+                        ..request.clone()
+                    },
+                    started_at: SystemTime::now(),
+                    finished_at: None,
+                    accounting: Accounting::default(),
+                    termination: None,
+                    output: Some(output_receiver),
+                    segments,
+                    stdin: input_sender,
+                };
+
+                if let Some(journal) = &self.journal {
+                    if let Err(err) = journal.started(&id, &process).await {
+                        warn!("Couldn't record the start of {}: {}", &id, err);
+                    }
+                }
 
                 let mut map = self.processes.write().await;
-                (*map).insert(id, (child.id().unwrap(), Running));
+                (*map).insert(id, process);
+                drop(map);
 
                 info!("Spawned child {} for {}", &sys_pid, &id);
 
+                // either piped above or the master side of the pseudo-terminal:
+                let (stdout, stderr, stdin): (OutputSource, OutputSource, Option<Box<dyn Stdin>>) =
+                    match master {
+                        Some((reader, writer)) => (
+                            Box::new(reader),
+                            Box::new(tokio::io::empty()),
+                            Some(Box::new(writer)),
+                        ),
+                        None => (
+                            Box::new(child.stdout.take().unwrap()),
+                            Box::new(child.stderr.take().unwrap()),
+                            child
+                                .stdin
+                                .take()
+                                .map(|stdin| Box::new(stdin) as Box<dyn Stdin>),
+                        ),
+                    };
+                let buffer_size = self.buffer_size.unwrap_or(256);
+
+                let captured = Arc::clone(&self.processes);
+
+                if let Some(stdin) = stdin {
+                    let initial = request.stdin.clone();
+
+                    tokio::spawn(async move {
+                        // the process can close its stdin before reading all of it
+                        if let Err(err) = input::feed(stdin, initial, input_receiver).await {
+                            info!("Couldn't write the input of {}: {}", &id, err);
+                        }
+                    });
+                }
+
+                let killed = Arc::clone(&self.processes);
+                let killed_request = request.clone();
+
+                // the whole job goes, so that none of its processes is left
+                // writing into the pipes no one reads anymore
+                let kill = move || {
+                    tokio::spawn(async move {
+                        if let Some(process) = killed.write().await.get_mut(&id) {
+                            process.termination = Some(Termination::LogQuotaExceeded);
+                        }
+
+                        if let Err(err) = kill_job(&id, sys_pid, &killed_request) {
+                            warn!("Couldn't kill {}: {}", &id, err);
+                        }
+                    });
+                };
+
+                // the followers of the logs are notified of the end of the
+                // capture by dropping the sender:
+                tokio::spawn(async move {
+                    if let Err(err) = output::capture(
+                        stdout,
+                        stderr,
+                        writer,
+                        buffer_size,
+                        &output_sender,
+                        id,
+                        kill,
+                    )
+                    .await
+                    {
+                        warn!("Couldn't capture the output of {}: {}", &id, err);
+                    }
+
+                    if let Some(process) = captured.write().await.get_mut(&id) {
+                        process.output = None;
+                    }
+
+                    drop(output_sender);
+                });
+
+                // it's fine for no one to be watching:
+                self.events.send(event(&id, Kind::Started)).ok();
+
+                if let Some(run_request::Timeout::TimeoutSeconds(seconds)) = request.timeout {
+                    self.enforce_timeout(id, Duration::from_secs(seconds));
+                }
+
                 tokio::spawn(async move {
                     // A fuller solution would be to kill child processes upon us
-                    // receiving SIGINT, SIGTERM or SIGQUIT. In order to do so
-                    // properly, a PID namespace would need to be unshared - to
-                    // defend against the "double-fork" daemoning where the second
-                    // one in reparented to the init process. This stays out of
-                    // scope of this work though.
+                    // receiving SIGINT, SIGTERM or SIGQUIT. The descendants of the
+                    // constrained processes can't escape their control groups,
+                    // even with the "double-fork" daemoning. The ones of the
+                    // others can by starting a session of their own though - a PID
+                    // namespace would need to be unshared to defend against it.
+                    // This stays out of scope of this work.
 
                     match child.wait().await {
                         Ok(exit_status) => {
+                            let accounting = cgroups.accounting();
+                            let termination = processes
+                                .read()
+                                .await
+                                .get(&id)
+                                .and_then(|process| process.termination);
+
+                            if let Some(journal) = &journal {
+                                if let Err(err) = journal
+                                    .finished(&id, exit_status, &accounting, termination)
+                                    .await
+                                {
+                                    warn!("Couldn't record the exit of {}: {}", &id, err);
+                                }
+                            }
+
+                            let oom_killed = accounting.oom_killed;
                             let mut map = processes.write().await;
-                            (*map).insert(id, (sys_pid, Stopped(exit_status)));
+
+                            if let Some(process) = (*map).get_mut(&id) {
+                                process.status = Stopped(exit_status);
+                                process.finished_at = Some(SystemTime::now());
+                                process.accounting = accounting;
+                                // nothing more gets written into its stdin
+                                process.stdin = None;
+                            }
+
+                            drop(map);
+
+                            events.send(exit_event(&id, exit_status, oom_killed)).ok();
                         }
                         Err(_) => {
                             warn!("Couldn't get the exit status for {}", &id);
                         }
                     }
 
+                    // the descendants left behind keep the control groups
+                    // busy until they exit
+                    while !cgroups.procs().is_empty() {
+                        tokio::time::sleep(Duration::from_millis(250)).await;
+                    }
+
                     if let Err(err) = cgroups.delete() {
                         warn!(
                             "Couldn't delete control group for {}: {}",
@@ -150,62 +797,45 @@ impl Runner {
         }
     }
 
-    /// Stops a running process if it was started by this instance of the Runner
+    /// Stops a running process if it was started by this instance of the Runner.
+    /// The process and all of its descendants are sent the requested signal
+    /// (SIGTERM by default) and get killed with SIGKILL if they don't exit
+    /// within the grace period
     ///
     /// # Panics
     ///
     /// Panics if called from outside of the Tokio runtime.
     pub async fn stop(&self, request: &StopRequest) -> Result<(), StopError> {
-        if let Ok(id) = Uuid::parse_str(&request.id) {
-            if let Some(pid) = self.pid_for_process(&id).await {
-                if let Some((_, Stopped(_))) = self.processes.read().await.get(&id) {
-                    return Err(stop_error::Error::ProcessAlreadyStoppedError.into());
-                }
+        let initial_signal = match request.signal {
+            0 => signal::Signal::SIGTERM,
+            number => signal::Signal::try_from(number)
+                .map_err(|_| stop_error::Error::InvalidSignalError)?,
+        };
 
-                let mut start: Option<Instant> = None;
+        let grace_period = match request.grace_period {
+            Some(stop_request::GracePeriod::GracePeriodSeconds(seconds)) => {
+                Duration::from_secs(seconds.into())
+            }
+            None => DEFAULT_GRACE_PERIOD,
+        };
 
-                let sigkill = || -> Result<(), StopError> {
-                    if signal::kill(Pid::from_raw(pid as i32), signal::Signal::SIGKILL).is_err() {
-                        return Err(stop_error::Error::CouldntStopError.into());
+        if let Ok(id) = Uuid::parse_str(&request.id) {
+            if let Some(pid) = self.pid_for_process(&id).await {
+                let run_request = match self.processes.read().await.get(&id) {
+                    Some(Process {
+                        status: Stopped(_), ..
+                    })
+                    | Some(Process { status: Lost, .. }) => {
+                        return Err(stop_error::Error::ProcessAlreadyStoppedError.into());
                     }
-
-                    Ok(())
+                    Some(process) => process.request.clone(),
+                    None => return Err(stop_error::Error::ProcessNotFoundError.into()),
                 };
 
-                while let Some((_, Running)) = self.processes.read().await.get(&id) {
-                    if start.is_some() && start.unwrap().elapsed().as_secs() > 5 {
-                        sigkill()?;
-                        break;
-                    } else {
-                        start = Some(Instant::now());
-
-                        match signal::kill(Pid::from_raw(pid as i32), signal::Signal::SIGTERM) {
-                            Ok(_) => {
-                                // let's give it a bit and re-check if the process
-                                // is still there in the next run of this loop
-
-                                tokio::time::sleep(Duration::from_millis(100)).await;
-                            }
-                            Err(err) => {
-                                if let nix::Error::Sys(errno) = err {
-                                    match errno {
-                                        Errno::EACCES | Errno::ECHILD | Errno::EPERM => {
-                                            return Err(anyhow!(errno.desc()).into());
-                                        }
-                                        Errno::ESRCH => break,
-                                        _ => {
-                                            sigkill()?;
-                                            break;
-                                        }
-                                    }
-                                }
-                                break;
-                            }
-                        }
-                    }
-                }
+                self.events.send(event(&id, Kind::StopRequested)).ok();
 
-                Ok(())
+                self.terminate(&id, pid, &run_request, initial_signal, grace_period)
+                    .await
             } else {
                 Err(stop_error::Error::ProcessNotFoundError.into())
             }
@@ -214,115 +844,459 @@ impl Runner {
         }
     }
 
-    /// Fetches the status of the process if it was started by this instanmce of the Runner.
-    /// If the process has finished, returns an exit code or the signal that killed it
-    pub async fn status(&self, request: &StatusRequest) -> Result<StatusResult, StatusError> {
-        if let Ok(id) = Uuid::parse_str(&request.id) {
-            let map = self.processes.read().await;
+    /// Sends a given signal to a process and all of its descendants, and kills
+    /// them with SIGKILL if they don't exit within the grace period. A paused
+    /// process gets thawed first
+    async fn terminate(
+        &self,
+        id: &Uuid,
+        pid: u32,
+        run_request: &RunRequest,
+        initial_signal: signal::Signal,
+        grace_period: Duration,
+    ) -> Result<(), StopError> {
+        // a frozen process doesn't handle the signals until it's thawed
+        if let Some(process) = self.processes.write().await.get_mut(id) {
+            if let Paused = process.status {
+                existing_cgroups(&process.request, id)
+                    .thaw()
+                    .context("Couldn't thaw the control groups")?;
+
+                process.status = Running;
+            }
+        }
 
-            if let Some((_, process_status)) = map.get(&id) {
-                match process_status {
-                    Stopped(status) => {
-                        let result = match status.code() {
-                            Some(code) => {
-                                status_result::Finish::Result(status_result::ExitResult {
-                                    exit: Some(status_result::exit_result::Exit::Code(code)),
-                                    kill: None,
-                                })
-                            }
-                            None => match status.signal() {
-                                Some(signal) => {
-                                    status_result::Finish::Result(status_result::ExitResult {
-                                        exit: None,
-                                        kill: Some(status_result::exit_result::Kill::Signal(
-                                            signal,
-                                        )),
-                                    })
-                                }
-                                None => {
-                                    return Err(anyhow!(
-                                        "Couldn't get exit code or the kill signal"
-                                    )
-                                    .into())
-                                }
-                            },
-                        };
+        let sigkill = || -> Result<(), StopError> {
+            if kill_job(id, pid, run_request).is_err() {
+                return Err(stop_error::Error::CouldntStopError.into());
+            }
+
+            Ok(())
+        };
+
+        let start = Instant::now();
 
-                        Ok(StatusResult {
-                            finish: Some(result),
-                        })
+        if let Err(err) = signal_job(id, pid, run_request, initial_signal) {
+            if let nix::Error::Sys(errno) = err {
+                match errno {
+                    Errno::EACCES | Errno::ECHILD | Errno::EPERM => {
+                        return Err(anyhow!(errno.desc()).into());
                     }
-                    Running => Ok(StatusResult { finish: None }),
+                    _ => sigkill()?,
                 }
-            } else {
-                Err(status_error::Error::ProcessNotFoundError.into())
             }
-        } else {
-            Err(status_error::Error::InvalidId.into())
+
+            return Ok(());
+        }
+
+        // let's give it a bit and re-check if the process or any of
+        // its descendants are still there until the grace period is over
+        loop {
+            let running = matches!(
+                self.processes.read().await.get(id),
+                Some(Process {
+                    status: Running,
+                    ..
+                })
+            );
+
+            if !running && !is_job_alive(id, pid, run_request) {
+                break;
+            }
+
+            if start.elapsed() > grace_period {
+                return sigkill();
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
         }
+
+        Ok(())
     }
 
-    /// Returns a stream of stdout or stderr logs for a process. The stream implements
-    /// futures::streams::Stream.
+    /// Stops a process once it runs for longer than a given time, the same
+    /// way as Stop does with its default signal and grace period
     ///
     /// # Panics
     ///
     /// Panics if called from outside of the Tokio runtime.
-    pub async fn log(
-        &self,
-        request: &LogRequest,
-    ) -> Result<
-        std::pin::Pin<Box<dyn Stream<Item = Result<Vec<u8>, LogError>> + Send + Sync>>,
-        LogError,
-    > {
-        let map = self.processes.read().await;
+    fn enforce_timeout(&self, id: Uuid, timeout: Duration) {
+        let runner = self.clone();
 
-        if let Ok(id) = Uuid::parse_str(&request.id) {
-            if map.get(&id).is_some() {
-                let maybe_descriptor = log_request::Descriptor::from_i32(request.descriptor);
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
 
-                match maybe_descriptor {
-                    Some(descriptor) => {
-                        let log_path = match descriptor {
-                            log_request::Descriptor::Stdout => self.stdout_path(&id),
-                            log_request::Descriptor::Stderr => self.stderr_path(&id),
-                        };
+            let (pid, run_request) = match runner.processes.write().await.get_mut(&id) {
+                Some(process) if matches!(process.status, Running | Paused) => {
+                    process.termination = Some(Termination::TimedOut);
+                    (process.pid, process.request.clone())
+                }
+                _ => return,
+            };
 
-                        let file = tokio::fs::File::open(&log_path).await.context("Couldn't open log file")?;
+            info!("Process {} timed out", &id);
 
-                        let buffer_size = self.buffer_size.unwrap_or(256);
-                        let buffer = vec![0_u8; buffer_size];
+            // it's fine for no one to be watching:
+            runner.events.send(event(&id, Kind::TimedOut)).ok();
 
-                        let state = StreamState {
-                            processes: Arc::clone(&self.processes),
-                            file,
-                            buffer,
-                            id,
-                            close: false
-                        };
+            if let Err(err) = runner
+                .terminate(
+                    &id,
+                    pid,
+                    &run_request,
+                    signal::Signal::SIGTERM,
+                    DEFAULT_GRACE_PERIOD,
+                )
+                .await
+            {
+                warn!(
+                    "Couldn't stop {} after its timeout: {}",
+                    &id, err.description
+                );
+            }
+        });
+    }
 
-                        Ok(Box::pin(unfold(state, |mut state| async move {
-                            if state.close {
-                                return None;
-                            }
+    /// Sends a signal to a running process or, when asked to, to all of its
+    /// descendants too. The signals sent to a paused process are handled once
+    /// it's resumed
+    pub async fn signal(&self, request: &SignalRequest) -> Result<(), SignalError> {
+        let id = match Uuid::parse_str(&request.id) {
+            Ok(id) => id,
+            Err(_) => return Err(signal_error::Error::InvalidId.into()),
+        };
 
-                            loop {
-                                if let Ok(bytes) = state.file.read(&mut state.buffer).await {
-                                    if bytes > 0 {
-                                        let data = state.buffer[0..bytes].to_vec();
-
-                                        return Some((Ok(data), state));
-                                    } else if let Some((_, Stopped(_))) = state.processes.read().await.get(&state.id) {
-                                        return None;
-                                    } else {
-                                        tokio::time::sleep(Duration::from_millis(100)).await;
-                                    }
-                                } else {
-                                    let state = StreamState { close: true, ..state };
+        let sig = match signal::Signal::try_from(request.signal) {
+            Ok(sig) => sig,
+            Err(_) => return Err(signal_error::Error::InvalidSignalError.into()),
+        };
 
-                                    return Some((Err(anyhow!("Error reading from log file").into()), state ));
-                                }
-                            }
+        let map = self.processes.read().await;
+
+        let process = match map.get(&id) {
+            Some(process) => process,
+            None => return Err(signal_error::Error::ProcessNotFoundError.into()),
+        };
+
+        if matches!(process.status, Stopped(_) | Lost) {
+            return Err(signal_error::Error::ProcessAlreadyStoppedError.into());
+        }
+
+        let sent = if request.group {
+            signal_job(&id, process.pid, &process.request, sig)
+        } else {
+            signal::kill(Pid::from_raw(process.pid as i32), sig)
+        };
+
+        match sent {
+            // exited in the meantime
+            Ok(_) | Err(nix::Error::Sys(Errno::ESRCH)) => (),
+            Err(err) => return Err(anyhow!(err).into()),
+        }
+
+        info!("Sent {} to {}", sig, &id);
+
+        Ok(())
+    }
+
+    /// Pauses a running process by freezing its control groups. Only the
+    /// constrained processes have control groups to be frozen
+    pub async fn pause(&self, request: &PauseRequest) -> Result<(), PauseError> {
+        let id = match Uuid::parse_str(&request.id) {
+            Ok(id) => id,
+            Err(_) => return Err(pause_error::Error::InvalidId.into()),
+        };
+
+        let mut map = self.processes.write().await;
+
+        let process = match map.get_mut(&id) {
+            Some(process) => process,
+            None => return Err(pause_error::Error::ProcessNotFoundError.into()),
+        };
+
+        match process.status {
+            Running if !is_constrained(&process.request) => {
+                return Err(pause_error::Error::NotConstrainedError.into())
+            }
+            Running => (),
+            Paused => return Err(pause_error::Error::ProcessAlreadyPausedError.into()),
+            Stopped(_) | Lost => return Err(pause_error::Error::ProcessAlreadyStoppedError.into()),
+        }
+
+        existing_cgroups(&process.request, &id)
+            .freeze()
+            .context("Couldn't freeze the control groups")?;
+
+        process.status = Paused;
+        drop(map);
+
+        info!("Paused {}", &id);
+
+        // it's fine for no one to be watching:
+        self.events.send(event(&id, Kind::Paused)).ok();
+
+        Ok(())
+    }
+
+    /// Resumes a paused process by thawing its control groups
+    pub async fn resume(&self, request: &ResumeRequest) -> Result<(), ResumeError> {
+        let id = match Uuid::parse_str(&request.id) {
+            Ok(id) => id,
+            Err(_) => return Err(resume_error::Error::InvalidId.into()),
+        };
+
+        let mut map = self.processes.write().await;
+
+        let process = match map.get_mut(&id) {
+            Some(process) => process,
+            None => return Err(resume_error::Error::ProcessNotFoundError.into()),
+        };
+
+        if !matches!(process.status, Paused) {
+            return Err(resume_error::Error::ProcessNotPausedError.into());
+        }
+
+        existing_cgroups(&process.request, &id)
+            .thaw()
+            .context("Couldn't thaw the control groups")?;
+
+        process.status = Running;
+        drop(map);
+
+        info!("Resumed {}", &id);
+
+        // it's fine for no one to be watching:
+        self.events.send(event(&id, Kind::Resumed)).ok();
+
+        Ok(())
+    }
+
+    /// Deletes a finished process along with its logs. The processes still
+    /// running or with their output still being captured can't be deleted
+    pub async fn delete(&self, request: &DeleteRequest) -> Result<(), DeleteError> {
+        match Uuid::parse_str(&request.id) {
+            Ok(id) => self.evict(&id).await,
+            Err(_) => Err(delete_error::Error::InvalidId.into()),
+        }
+    }
+
+    /// Deletes the finished processes going over the retention policies,
+    /// the oldest first. Returns the number of the deleted processes
+    pub async fn collect(&self) -> usize {
+        let map = self.processes.read().await;
+
+        let mut finished = map
+            .iter()
+            .filter(|(_, process)| is_deletable(process))
+            .filter_map(|(id, process)| process.finished_at.map(|finished_at| (finished_at, *id)))
+            .collect::<Vec<_>>();
+
+        drop(map);
+
+        finished.sort();
+
+        let mut deleted = 0;
+
+        for (position, (finished_at, id)) in finished.iter().enumerate() {
+            let kept = finished.len() - position;
+            let log_usage = self.log_usage.load(Ordering::SeqCst);
+
+            // the ones that finished later aren't evicted either
+            if !self.retention.evicts(*finished_at, kept, log_usage) {
+                break;
+            }
+
+            match self.evict(id).await {
+                Ok(_) => {
+                    info!("Deleted process {} as per the retention policies", &id);
+                    deleted += 1;
+                }
+                Err(err) => warn!("Couldn't delete process {}: {}", &id, err.description),
+            }
+        }
+
+        deleted
+    }
+
+    /// Removes a finished process from the map, the journal and its logs
+    /// from the disk
+    async fn evict(&self, id: &Uuid) -> Result<(), DeleteError> {
+        let mut map = self.processes.write().await;
+
+        let segments = match map.get(id) {
+            Some(process) if is_deletable(process) => process.segments.clone(),
+            Some(_) => return Err(delete_error::Error::ProcessRunningError.into()),
+            None => return Err(delete_error::Error::ProcessNotFoundError.into()),
+        };
+
+        map.remove(id);
+
+        drop(map);
+
+        if let Some(journal) = &self.journal {
+            if let Err(err) = journal.deleted(id).await {
+                warn!("Couldn't record the deletion of {}: {}", &id, err);
+            }
+        }
+
+        let size = LogPaths::new(&self.log_dir, id, &segments)
+            .remove_all()
+            .await?;
+        self.log_usage.fetch_sub(size, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    /// Returns the resource usage of the process as accounted by its control
+    /// groups: the usage so far while it's running and its totals once it's
+    /// finished
+    pub async fn stats(&self, request: &StatsRequest) -> Result<ResourceUsage, StatsError> {
+        let id = match Uuid::parse_str(&request.id) {
+            Ok(id) => id,
+            Err(_) => return Err(stats_error::Error::InvalidId.into()),
+        };
+
+        match self.processes.read().await.get(&id) {
+            Some(process) => match usage(&id, process) {
+                Some(usage) => Ok(usage),
+                None => Err(stats_error::Error::NotAccountedError.into()),
+            },
+            None => Err(stats_error::Error::ProcessNotFoundError.into()),
+        }
+    }
+
+    /// Returns a stream of the samples of the resource usage of the process
+    /// taken at the requested interval, ending with its totals once it's
+    /// finished.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of the Tokio runtime.
+    pub async fn stats_follow(
+        &self,
+        request: &StatsRequest,
+    ) -> Result<
+        std::pin::Pin<Box<dyn Stream<Item = Result<ResourceUsage, StatsError>> + Send + Sync>>,
+        StatsError,
+    > {
+        // fails the same way as the first sample would
+        self.stats(request).await?;
+
+        let id = Uuid::parse_str(&request.id).map_err(|_| stats_error::Error::InvalidId)?;
+        let interval = match request.interval_ms {
+            0 => DEFAULT_STATS_INTERVAL,
+            millis => Duration::from_millis(millis.into()),
+        };
+
+        Ok(Box::pin(unfold(
+            Some((self.clone(), true)),
+            move |state| async move {
+                let (runner, first) = state?;
+
+                if !first {
+                    tokio::time::sleep(interval).await;
+                }
+
+                let (sample, running) = match runner.processes.read().await.get(&id) {
+                    Some(process) => match usage(&id, process) {
+                        Some(usage) => (Ok(usage), matches!(process.status, Running | Paused)),
+                        None => (Err(stats_error::Error::NotAccountedError.into()), false),
+                    },
+                    // deleted in the meantime
+                    None => (Err(stats_error::Error::ProcessNotFoundError.into()), false),
+                };
+
+                Some((sample, if running { Some((runner, false)) } else { None }))
+            },
+        )))
+    }
+
+    /// Fetches the status of the process if it was started by this instanmce of the Runner.
+    /// If the process has finished, returns an exit code or the signal that killed it
+    pub async fn status(&self, request: &StatusRequest) -> Result<StatusResult, StatusError> {
+        if let Ok(id) = Uuid::parse_str(&request.id) {
+            let map = self.processes.read().await;
+
+            if let Some(process) = map.get(&id) {
+                Ok(to_status_result(&id, process)?)
+            } else {
+                Err(status_error::Error::ProcessNotFoundError.into())
+            }
+        } else {
+            Err(status_error::Error::InvalidId.into())
+        }
+    }
+
+    /// Returns a stream of stdout or stderr logs for a process. The stream implements
+    /// futures::streams::Stream. It starts at the requested offset or with the
+    /// requested number of the last lines and follows the new output if asked to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of the Tokio runtime.
+    pub async fn log(
+        &self,
+        request: &LogRequest,
+    ) -> Result<
+        std::pin::Pin<Box<dyn Stream<Item = Result<Chunk, LogError>> + Send + Sync>>,
+        LogError,
+    > {
+        let map = self.processes.read().await;
+
+        if let Ok(id) = Uuid::parse_str(&request.id) {
+            if let Some(process) = map.get(&id) {
+                let maybe_descriptor = log_request::Descriptor::from_i32(request.descriptor);
+
+                match maybe_descriptor {
+                    Some(log_request::Descriptor::Both) if request.start_offset > 0 => {
+                        Err(log_error::Error::InvalidRange.into())
+                    }
+                    Some(_) if request.start_offset > 0 && request.tail_lines > 0 => {
+                        Err(log_error::Error::InvalidRange.into())
+                    }
+                    Some(descriptor) => {
+                        let paths = LogPaths::new(&self.log_dir, &id, &process.segments);
+
+                        let mut state = StreamState {
+                            output: if request.follow { process.output.clone() } else { None },
+                            reader: Reader::open(paths.clone()).await?,
+                            descriptor,
+                            pending: None,
+                            stdout_start: request.start_offset,
+                            stderr_start: request.start_offset,
+                            buffer_size: self.buffer_size.unwrap_or(256),
+                            close: false
+                        };
+
+                        if request.tail_lines > 0 {
+                            state.tail(&paths, request.tail_lines).await?;
+                        }
+
+                        Ok(Box::pin(unfold(state, |mut state| async move {
+                            if state.close {
+                                return None;
+                            }
+
+                            loop {
+                                match state.next_chunk().await {
+                                    Ok(Some(chunk)) => return Some((Ok(chunk), state)),
+                                    Ok(None) => match &mut state.output {
+                                        // the changes made since the last read
+                                        // are also noticed here
+                                        Some(output) => if output.changed().await.is_err() {
+                                            // the capture is over, the rest of
+                                            // the output gets read once more
+                                            state.output = None;
+                                        }
+                                        None => return None,
+                                    },
+                                    Err(_) => {
+                                        let state = StreamState { close: true, ..state };
+
+                                        return Some((Err(anyhow!("Error reading from log file").into()), state ));
+                                    }
+                                }
+                            }
 
                         })))
                     }
@@ -333,216 +1307,2326 @@ impl Runner {
             } else {
                 Err(log_error::Error::ProcessNotFoundError.into())
             }
-        } else {
-            Err(log_error::Error::InvalidId.into())
+        } else {
+            Err(log_error::Error::InvalidId.into())
+        }
+    }
+
+    /// Lists the processes known to the Runner, ordered by their start time.
+    /// Returns a page of the processes matching the request's filters and the
+    /// token to fetch the next page with (empty when there are no more pages)
+    pub async fn list(&self, request: &ListRequest) -> Result<(Vec<Job>, String), ListError> {
+        let after = if request.page_token.is_empty() {
+            None
+        } else {
+            match parse_page_token(&request.page_token) {
+                Some(key) => Some(key),
+                None => return Err(list_error::Error::InvalidPageToken.into()),
+            }
+        };
+
+        let state =
+            match list_request::State::from_i32(request.state) {
+                Some(state) => state,
+                None => return Err(InternalError {
+                    description:
+                        "Given state is invalid. Are you using compatible version of the client?"
+                            .to_string(),
+                }
+                .into()),
+            };
+
+        let page_size = match request.page_size as usize {
+            0 => DEFAULT_PAGE_SIZE,
+            size => size.min(MAX_PAGE_SIZE),
+        };
+
+        let map = self.processes.read().await;
+
+        let mut matching = map
+            .iter()
+            .map(|(id, process)| ((to_millis(process.started_at), *id), process))
+            .filter(|(key, process)| {
+                let state_matches = matches!(
+                    (state, &process.status),
+                    (list_request::State::Any, _)
+                        | (list_request::State::Running, Running)
+                        | (list_request::State::Paused, Paused)
+                        | (list_request::State::Stopped, Stopped(_))
+                        | (list_request::State::Stopped, Lost)
+                );
+
+                let after_page_token = match after {
+                    Some(after) => *key > after,
+                    None => true,
+                };
+
+                state_matches
+                    && after_page_token
+                    && process.request.command.starts_with(&request.command_prefix)
+                    && key.0 > request.started_after
+            })
+            .collect::<Vec<_>>();
+
+        matching.sort_by_key(|(key, _)| *key);
+
+        let next_page_token = if matching.len() > page_size {
+            let (started_at, id) = matching[page_size - 1].0;
+
+            format!("{}:{}", started_at, id)
+        } else {
+            String::new()
+        };
+
+        let mut jobs = Vec::with_capacity(page_size);
+
+        for ((started_at, id), process) in matching.into_iter().take(page_size) {
+            jobs.push(Job {
+                id: id.to_string(),
+                command: process.request.command.clone(),
+                arguments: process.request.arguments.clone(),
+                started_at,
+                finish: process.finished_at.map(|finished_at| {
+                    list_response::job::Finish::FinishedAt(to_millis(finished_at))
+                }),
+                status: Some(to_status_result(&id, process)?),
+            });
+        }
+
+        Ok((jobs, next_page_token))
+    }
+
+    /// Waits for the process to finish and returns its status. Gives up with
+    /// an error after the timeout, when one is given.
+    pub async fn wait(&self, request: &WaitRequest) -> Result<StatusResult, WaitError> {
+        let id = match Uuid::parse_str(&request.id) {
+            Ok(id) => id,
+            Err(_) => return Err(wait_error::Error::InvalidId.into()),
+        };
+
+        // subscribing before checking the status not to miss
+        // the exit happening in between
+        let mut receiver = self.events.subscribe();
+
+        let wait = async {
+            loop {
+                match self.processes.read().await.get(&id) {
+                    Some(Process {
+                        status: Running, ..
+                    })
+                    | Some(Process { status: Paused, .. }) => (),
+                    Some(process) => return Ok(to_status_result(&id, process)?),
+                    None => return Err(wait_error::Error::ProcessNotFoundError.into()),
+                }
+
+                // any event of the process or missing some is a reason to re-check
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) if event.id != id.to_string() => continue,
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => break,
+                        Err(broadcast::error::RecvError::Closed) => {
+                            return Err(anyhow!("Runner is shutting down").into())
+                        }
+                    }
+                }
+            }
+        };
+
+        match request.timeout {
+            Some(wait_request::Timeout::TimeoutSeconds(seconds)) => {
+                match tokio::time::timeout(Duration::from_secs(seconds), wait).await {
+                    Ok(result) => result,
+                    Err(_) => Err(wait_error::Error::TimeoutError.into()),
+                }
+            }
+            None => wait.await,
+        }
+    }
+
+    /// Returns a stream of lifecycle events of the processes with given ids
+    /// or of all processes when no ids are given. The events are streamed
+    /// as they happen - past ones aren't replayed.
+    pub async fn watch(
+        &self,
+        request: &WatchRequest,
+    ) -> Result<
+        std::pin::Pin<Box<dyn Stream<Item = Result<Event, WatchError>> + Send + Sync>>,
+        WatchError,
+    > {
+        let mut ids = HashSet::new();
+
+        for id in &request.ids {
+            match Uuid::parse_str(id) {
+                Ok(id) => {
+                    ids.insert(id.to_string());
+                }
+                Err(_) => return Err(watch_error::Error::InvalidId.into()),
+            }
+        }
+
+        let receiver = self.events.subscribe();
+
+        Ok(Box::pin(unfold(
+            (receiver, ids),
+            |(mut receiver, ids)| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) => {
+                            if ids.is_empty() || ids.contains(&event.id) {
+                                return Some((Ok(event), (receiver, ids)));
+                            }
+                        }
+                        Err(broadcast::error::RecvError::Lagged(missed)) => {
+                            let err = WatchError {
+                                description: format!("Missed {} events", missed),
+                                errors: Some(watch_error::Errors::WatchError(
+                                    watch_error::Error::EventsMissed as i32,
+                                )),
+                            };
+
+                            return Some((Err(err), (receiver, ids)));
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            },
+        )))
+    }
+
+    /// Returns a stream of the output of the process on both of its descriptors,
+    /// starting with the given number of the last lines (all of them when 0),
+    /// followed by the status of the process once it finishes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of the Tokio runtime.
+    pub async fn follow(
+        &self,
+        id: &Uuid,
+        tail_lines: u32,
+    ) -> Result<
+        std::pin::Pin<Box<dyn Stream<Item = run_follow_response::Results> + Send + Sync>>,
+        LogError,
+    > {
+        let output = self
+            .log(&LogRequest {
+                id: id.to_string(),
+                descriptor: log_request::Descriptor::Both as i32,
+                tail_lines,
+                follow: true,
+                ..Default::default()
+            })
+            .await?
+            .map(|item| match item {
+                Ok(chunk) => run_follow_response::Results::Output(run_follow_response::Output {
+                    descriptor: chunk.descriptor,
+                    data: chunk.data,
+                }),
+                Err(err) => run_follow_response::Results::LogError(err),
+            });
+
+        let runner = self.clone();
+        let wait_request = WaitRequest {
+            id: id.to_string(),
+            timeout: None,
+        };
+
+        // the log stream ends once the process is stopped
+        let status = stream::once(async move {
+            match runner.wait(&wait_request).await {
+                Ok(result) => run_follow_response::Results::Status(result),
+                Err(err) => run_follow_response::Results::LogError(anyhow!(err.description).into()),
+            }
+        });
+
+        Ok(Box::pin(output.chain(status)))
+    }
+
+    /// Attaches to a process: writes the input of the first request of the
+    /// attached client into the stdin of the process and returns the stream
+    /// of its output the same way as follow does, telling first whether the
+    /// process runs in a pseudo-terminal.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from outside of the Tokio runtime.
+    pub async fn attach(
+        &self,
+        request: &AttachRequest,
+    ) -> Result<
+        std::pin::Pin<Box<dyn Stream<Item = attach_response::Results> + Send + Sync>>,
+        AttachError,
+    > {
+        let id = Uuid::parse_str(&request.id).map_err(|_| attach_error::Error::InvalidId)?;
+
+        let tty = match self.processes.read().await.get(&id) {
+            Some(process) => process.request.tty,
+            None => return Err(attach_error::Error::ProcessNotFoundError.into()),
+        };
+
+        if !request.input.is_empty() || request.close_stdin || request.resize.is_some() {
+            self.write_stdin(&id, request).await?;
+        }
+
+        let output = self
+            .follow(&id, request.tail_lines)
+            .await
+            .map_err(|err| AttachError::from(anyhow!(err.description)))?
+            .filter_map(|item| async move {
+                match item {
+                    run_follow_response::Results::Output(output) => {
+                        Some(attach_response::Results::Output(output))
+                    }
+                    run_follow_response::Results::Status(status) => {
+                        Some(attach_response::Results::Status(status))
+                    }
+                    run_follow_response::Results::LogError(err) => {
+                        Some(attach_response::Results::LogError(err))
+                    }
+                    _ => None,
+                }
+            });
+
+        let attached = stream::once(async move { attach_response::Results::Tty(tty) });
+
+        Ok(Box::pin(attached.chain(output)))
+    }
+
+    /// Writes the input of one of the requests of an attached client into
+    /// the stdin of the process, closing it afterwards when requested to.
+    /// Resizes the pseudo-terminal of the process first when requested to.
+    /// Waits when the process doesn't keep up with reading its input
+    pub async fn write_stdin(&self, id: &Uuid, request: &AttachRequest) -> Result<(), AttachError> {
+        let (stdin, tty) = match self.processes.read().await.get(id) {
+            Some(process) => (process.stdin.clone(), process.request.tty),
+            None => return Err(attach_error::Error::ProcessNotFoundError.into()),
+        };
+
+        if request.resize.is_some() && !tty {
+            return Err(attach_error::Error::NotTtyError.into());
+        }
+
+        let stdin = stdin.ok_or(attach_error::Error::StdinClosedError)?;
+
+        if let Some(size) = &request.resize {
+            stdin
+                .send(Input::Resize(size.clone()))
+                .await
+                .map_err(|_| attach_error::Error::StdinClosedError)?;
+        }
+
+        if !request.input.is_empty() {
+            stdin
+                .send(Input::Data(request.input.clone()))
+                .await
+                .map_err(|_| attach_error::Error::StdinClosedError)?;
+        }
+
+        if request.close_stdin {
+            // a pseudo-terminal doesn't end along with the input, its
+            // processes read the end of the file on the EOF character instead
+            // and can keep reading afterwards, as a shell does
+            if tty {
+                stdin
+                    .send(Input::Data(vec![pty::EOF]))
+                    .await
+                    .map_err(|_| attach_error::Error::StdinClosedError)?;
+            } else if let Some(process) = self.processes.write().await.get_mut(id) {
+                process.stdin = None;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the PID for a given UUID id of the process
+    async fn pid_for_process(&self, id: &Uuid) -> Option<u32> {
+        let processes = self.processes.read().await;
+
+        (*processes).get(id).map(|process| process.pid)
+    }
+}
+
+/// Tells if an environment variable can be passed to a process: its name
+/// can't be empty nor contain "=" and neither can contain the NUL character
+fn is_valid_env(key: &str, value: &str) -> bool {
+    !key.is_empty() && !key.contains(['=', '\0']) && !value.contains('\0')
+}
+
+/// Tells if the process is finished and its output is fully captured
+fn is_deletable(process: &Process) -> bool {
+    matches!(process.status, Stopped(_) | Lost) && process.output.is_none()
+}
+
+/// Tells why a finished process got stopped when it's known to the server.
+/// The OOM killer only counts when the process got killed by a signal
+fn reason(process: &Process, killed: bool) -> status_result::exit_result::Reason {
+    match process.termination {
+        Some(Termination::TimedOut) => status_result::exit_result::Reason::TimedOut,
+        Some(Termination::LogQuotaExceeded) => status_result::exit_result::Reason::LogQuotaExceeded,
+        None if killed && process.accounting.oom_killed => {
+            status_result::exit_result::Reason::OomKilled
+        }
+        None => status_result::exit_result::Reason::NoReason,
+    }
+}
+
+/// Converts the status of a process into its representation in the service.
+/// The pids limit hits of a running process are read from its control groups
+fn to_status_result(id: &Uuid, process: &Process) -> Result<StatusResult> {
+    let pids_limit_hits = match process.status {
+        Running | Paused => existing_cgroups(&process.request, id).pids_limit_hits(),
+        _ => process.accounting.pids_limit_hits,
+    };
+
+    match &process.status {
+        Stopped(status) => {
+            let result = match status.code() {
+                Some(code) => status_result::Finish::Result(status_result::ExitResult {
+                    exit: Some(status_result::exit_result::Exit::Code(code)),
+                    reason: reason(process, false) as i32,
+                    peak_memory: process.accounting.usage.peak_memory,
+                    usage: usage(id, process),
+                    ..Default::default()
+                }),
+                None => match status.signal() {
+                    Some(signal) => status_result::Finish::Result(status_result::ExitResult {
+                        kill: Some(status_result::exit_result::Kill::Signal(signal)),
+                        reason: reason(process, true) as i32,
+                        peak_memory: process.accounting.usage.peak_memory,
+                        usage: usage(id, process),
+                        ..Default::default()
+                    }),
+                    None => return Err(anyhow!("Couldn't get exit code or the kill signal")),
+                },
+            };
+
+            Ok(StatusResult {
+                finish: Some(result),
+                pids_limit_hits,
+                paused: false,
+            })
+        }
+        Running => Ok(StatusResult {
+            finish: None,
+            pids_limit_hits,
+            paused: false,
+        }),
+        Paused => Ok(StatusResult {
+            finish: None,
+            pids_limit_hits,
+            paused: true,
+        }),
+        Lost => Ok(StatusResult {
+            finish: Some(status_result::Finish::Result(status_result::ExitResult {
+                lost: true,
+                ..Default::default()
+            })),
+            pids_limit_hits,
+            paused: false,
+        }),
+    }
+}
+
+/// Returns the resource usage of a process: read from its control groups
+/// while it's running and the totals recorded once it's finished. None when
+/// it isn't accounted
+fn usage(id: &Uuid, process: &Process) -> Option<ResourceUsage> {
+    if !is_constrained(&process.request) {
+        return None;
+    }
+
+    match process.status {
+        Running | Paused => Some(existing_cgroups(&process.request, id).usage()),
+        Stopped(_) => Some(ResourceUsage {
+            memory: 0,
+            tasks: 0,
+            ..process.accounting.usage.clone()
+        }),
+        Lost => None,
+    }
+}
+
+/// Sends a signal to all of the processes of a job: the ones in its control
+/// groups when it's constrained and the ones in its process group otherwise.
+/// The main process is signalled alone when it doesn't lead a process group,
+/// e.g. when it was started by an older version of the server
+fn signal_job(id: &Uuid, pid: u32, request: &RunRequest, sig: signal::Signal) -> nix::Result<()> {
+    let pids = if is_constrained(request) {
+        existing_cgroups(request, id).procs()
+    } else {
+        match signal::killpg(Pid::from_raw(pid as i32), sig) {
+            Err(nix::Error::Sys(Errno::ESRCH)) => vec![pid],
+            result => return result,
+        }
+    };
+
+    for pid in pids {
+        match signal::kill(Pid::from_raw(pid as i32), sig) {
+            // exited in the meantime
+            Ok(_) | Err(nix::Error::Sys(Errno::ESRCH)) => (),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
+
+/// Kills all of the processes of a job with SIGKILL
+fn kill_job(id: &Uuid, pid: u32, request: &RunRequest) -> Result<()> {
+    if is_constrained(request) {
+        existing_cgroups(request, id).kill()
+    } else {
+        Ok(signal_job(id, pid, request, signal::Signal::SIGKILL)?)
+    }
+}
+
+/// Tells if any of the processes of a job is still there
+fn is_job_alive(id: &Uuid, pid: u32, request: &RunRequest) -> bool {
+    if is_constrained(request) {
+        !existing_cgroups(request, id).procs().is_empty()
+    } else {
+        signal::killpg(Pid::from_raw(pid as i32), None).is_ok()
+    }
+}
+
+/// Creates a lifecycle event of a given kind that happened just now
+fn event(id: &Uuid, kind: Kind) -> Event {
+    Event {
+        id: id.to_string(),
+        kind: kind as i32,
+        time: to_millis(SystemTime::now()),
+        ..Default::default()
+    }
+}
+
+/// Creates a lifecycle event for the process that exited with given status
+fn exit_event(id: &Uuid, exit_status: ExitStatus, oom_killed: bool) -> Event {
+    match (exit_status.code(), exit_status.signal()) {
+        (Some(exit_code), _) => Event {
+            exit_code,
+            ..event(id, Kind::Exited)
+        },
+        (None, Some(signal)) if oom_killed => Event {
+            signal,
+            ..event(id, Kind::OomKilled)
+        },
+        (None, Some(signal)) => Event {
+            signal,
+            ..event(id, Kind::Killed)
+        },
+        (None, None) => event(id, Kind::Lost),
+    }
+}
+
+/// Parses the page token as returned from Runner::list. The token is made of
+/// the start time and the id of the last process on the previous page
+fn parse_page_token(token: &str) -> Option<(u64, Uuid)> {
+    let mut parts = token.splitn(2, ':');
+
+    let started_at = parts.next()?.parse().ok()?;
+    let id = Uuid::parse_str(parts.next()?).ok()?;
+
+    Some((started_at, id))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate sysinfo;
+    extern crate tokio;
+
+    use super::*;
+    use futures::StreamExt;
+    use service::TerminalSize;
+    use std::path::PathBuf;
+    use sysinfo::SystemExt;
+
+    #[tokio::test]
+    async fn proper_run_returns_correct_uuid() {
+        let runner = Runner::default();
+
+        let request = request("date", &[]);
+
+        runner.run(&request).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn incorrect_run_returns_error() {
+        let runner = Runner::default();
+
+        let request = request("", &[]);
+
+        let res = runner.run(&request).await;
+
+        assert!(res.is_err());
+        assert!(
+            res.err().unwrap().errors.unwrap()
+                == service::run_response::run_error::Errors::RunError(
+                    service::run_response::run_error::Error::NameEmptyError as i32
+                )
+        );
+    }
+
+    #[tokio::test]
+    async fn run_with_invalid_cpu_constraints_returns_error() {
+        let runner = Runner::default();
+
+        let quota = runner
+            .run(&RunRequest {
+                cpu_quota: Some(run_request::CpuQuota::MaxCpus(0.001)),
+                ..request("date", &[])
+            })
+            .await;
+
+        // would overflow the quota of the CPU time given to the kernel
+        let huge_quota = runner
+            .run(&RunRequest {
+                cpu_quota: Some(run_request::CpuQuota::MaxCpus(1e15)),
+                ..request("date", &[])
+            })
+            .await;
+
+        // more than the CPUs of the cpuset
+        let cpuset_quota = runner
+            .run(&RunRequest {
+                cpu_quota: Some(run_request::CpuQuota::MaxCpus(2.0)),
+                cpuset_cpus: "0".to_string(),
+                ..request("date", &[])
+            })
+            .await;
+
+        let cpuset = runner
+            .run(&RunRequest {
+                cpuset_cpus: "0-".to_string(),
+                ..request("date", &[])
+            })
+            .await;
+
+        assert!(
+            quota.err().unwrap().errors.unwrap()
+                == run_error::Errors::RunError(run_error::Error::InvalidCpuQuotaError as i32)
+        );
+        assert!(
+            huge_quota.err().unwrap().errors.unwrap()
+                == run_error::Errors::RunError(run_error::Error::InvalidCpuQuotaError as i32)
+        );
+        assert!(
+            cpuset_quota.err().unwrap().errors.unwrap()
+                == run_error::Errors::RunError(run_error::Error::InvalidCpuQuotaError as i32)
+        );
+        assert!(
+            cpuset.err().unwrap().errors.unwrap()
+                == run_error::Errors::RunError(run_error::Error::InvalidCpusetError as i32)
+        );
+    }
+
+    #[tokio::test]
+    async fn run_with_invalid_disk_rules_returns_error() {
+        let runner = Runner::default();
+
+        let unknown = runner
+            .run(&RunRequest {
+                disk_rules: vec![run_request::DiskRule {
+                    device: "/dev/null".to_string(),
+                    read_bps: 1024,
+                    ..Default::default()
+                }],
+                ..request("date", &[])
+            })
+            .await;
+
+        let numbers = runner
+            .run(&RunRequest {
+                disk_rules: vec![run_request::DiskRule {
+                    device: "4095:4095".to_string(),
+                    read_bps: 1024,
+                    ..Default::default()
+                }],
+                ..request("date", &[])
+            })
+            .await;
+
+        let unknown = unknown.err().unwrap();
+
+        assert!(
+            unknown.errors.unwrap()
+                == run_error::Errors::RunError(run_error::Error::UnknownDeviceError as i32)
+        );
+        assert!(unknown.description.contains("/dev/null"));
+        assert!(
+            numbers.err().unwrap().errors.unwrap()
+                == run_error::Errors::RunError(run_error::Error::UnknownDeviceError as i32)
+        );
+    }
+
+    #[tokio::test]
+    async fn run_with_invalid_env_or_working_dir_returns_error() {
+        let runner = Runner::default();
+
+        let working_dir = runner
+            .run(&RunRequest {
+                working_dir: "/idontexistnowhere".to_string(),
+                ..request("date", &[])
+            })
+            .await;
+
+        let env = runner
+            .run(&RunRequest {
+                env: vec![("A=B".to_string(), "C".to_string())]
+                    .into_iter()
+                    .collect(),
+                ..request("date", &[])
+            })
+            .await;
+
+        let working_dir = working_dir.err().unwrap();
+
+        assert!(
+            working_dir.errors.unwrap()
+                == run_error::Errors::RunError(run_error::Error::InvalidWorkingDirError as i32)
+        );
+        assert!(working_dir.description.contains("/idontexistnowhere"));
+        assert!(
+            env.err().unwrap().errors.unwrap()
+                == run_error::Errors::RunError(run_error::Error::InvalidEnvError as i32)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stdin_gets_fed_to_the_process() {
+        let runner = Runner::default();
+
+        let id = runner
+            .run(&RunRequest {
+                stdin: b"b\nc\na\n".to_vec(),
+                ..request("sort", &[])
+            })
+            .await
+            .unwrap();
+
+        assert!(read_output(&runner, &id).await == b"a\nb\nc\n");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn attached_input_gets_written_until_stdin_gets_closed() {
+        let runner = Runner::default();
+
+        let id = runner
+            .run(&RunRequest {
+                stdin: b"hello ".to_vec(),
+                open_stdin: true,
+                ..request("cat", &[])
+            })
+            .await
+            .unwrap();
+
+        let mut output = runner
+            .attach(&AttachRequest {
+                id: id.to_string(),
+                input: b"world".to_vec(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        runner
+            .write_stdin(
+                &id,
+                &AttachRequest {
+                    close_stdin: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let mut received = Vec::new();
+        let mut finished = false;
+
+        while let Some(results) = output.next().await {
+            match results {
+                attach_response::Results::Output(chunk) => received.extend(chunk.data),
+                attach_response::Results::Status(_) => finished = true,
+                attach_response::Results::Tty(tty) => assert!(!tty),
+                _ => panic!("unexpected results"),
+            }
+        }
+
+        let closed = runner
+            .write_stdin(
+                &id,
+                &AttachRequest {
+                    input: b"again".to_vec(),
+                    ..Default::default()
+                },
+            )
+            .await;
+
+        assert!(received == b"hello world");
+        assert!(finished);
+        assert!(
+            closed.err().unwrap().errors.unwrap()
+                == attach_error::Errors::AttachError(attach_error::Error::StdinClosedError as i32)
+        );
+    }
+
+    #[tokio::test]
+    async fn tty_gets_resized_and_reads_eof_each_time_stdin_gets_closed() {
+        let runner = Runner::default();
+
+        let id = runner
+            .run(&RunRequest {
+                tty: true,
+                terminal_size: Some(TerminalSize {
+                    rows: 24,
+                    columns: 80,
+                }),
+                ..request("sh", &["-c", "read _; stty size; tty; cat; cat"])
+            })
+            .await
+            .unwrap();
+
+        let mut output = runner
+            .attach(&AttachRequest {
+                id: id.to_string(),
+                resize: Some(TerminalSize {
+                    rows: 30,
+                    columns: 100,
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        for request in &[
+            AttachRequest {
+                input: b"go\n".to_vec(),
+                ..Default::default()
+            },
+            AttachRequest {
+                close_stdin: true,
+                ..Default::default()
+            },
+            AttachRequest {
+                close_stdin: true,
+                ..Default::default()
+            },
+        ] {
+            runner.write_stdin(&id, request).await.unwrap();
+        }
+
+        let mut received = Vec::new();
+        let mut attached_tty = false;
+        let mut finished = false;
+
+        while let Some(results) = output.next().await {
+            match results {
+                attach_response::Results::Output(chunk) => received.extend(chunk.data),
+                attach_response::Results::Status(_) => finished = true,
+                attach_response::Results::Tty(tty) => attached_tty = tty,
+                _ => panic!("unexpected results"),
+            }
+        }
+
+        let received = String::from_utf8_lossy(&received);
+
+        assert!(attached_tty);
+        assert!(finished);
+        assert!(received.contains("30 100"));
+        assert!(received.contains("/dev/pts/"));
+    }
+
+    #[tokio::test]
+    async fn resize_without_tty_returns_error() {
+        let runner = Runner::default();
+
+        let id = runner
+            .run(&RunRequest {
+                open_stdin: true,
+                ..request("cat", &[])
+            })
+            .await
+            .unwrap();
+
+        let resized = runner
+            .write_stdin(
+                &id,
+                &AttachRequest {
+                    resize: Some(TerminalSize {
+                        rows: 30,
+                        columns: 100,
+                    }),
+                    ..Default::default()
+                },
+            )
+            .await;
+
+        runner
+            .write_stdin(
+                &id,
+                &AttachRequest {
+                    close_stdin: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        assert!(
+            resized.err().unwrap().errors.unwrap()
+                == attach_error::Errors::AttachError(attach_error::Error::NotTtyError as i32)
+        );
+    }
+
+    #[tokio::test]
+    async fn run_as_unknown_user_or_in_foreign_group_returns_error() {
+        let runner = Runner::default();
+
+        let user = runner
+            .run(&RunRequest {
+                user: "idontexistnowhere".to_string(),
+                ..request("date", &[])
+            })
+            .await;
+
+        let group = runner
+            .run(&RunRequest {
+                supplementary_groups: vec!["idontexistnowhere".to_string()],
+                ..request("date", &[])
+            })
+            .await;
+
+        // nobody isn't a member of the root group
+        let foreign = runner
+            .run(&RunRequest {
+                user: "nobody".to_string(),
+                group: "0".to_string(),
+                ..request("date", &[])
+            })
+            .await;
+
+        assert!(
+            user.err().unwrap().errors.unwrap()
+                == run_error::Errors::RunError(run_error::Error::UnknownUserError as i32)
+        );
+        assert!(
+            group.err().unwrap().errors.unwrap()
+                == run_error::Errors::RunError(run_error::Error::UnknownGroupError as i32)
+        );
+        assert!(
+            foreign.err().unwrap().errors.unwrap()
+                == run_error::Errors::RunError(run_error::Error::GroupNotAllowedError as i32)
+        );
+    }
+
+    #[test]
+    fn users_policy_allows_the_listed_users_only() {
+        let policy = UsersPolicy::new(
+            vec![
+                ("client".to_string(), vec!["nobody".to_string()]),
+                ("admin".to_string(), vec!["*".to_string()]),
+                ("client".to_string(), vec!["daemon".to_string()]),
+            ],
+            None,
+        )
+        .unwrap();
+
+        let request = |user: &str| RunRequest {
+            user: user.to_string(),
+            ..request("date", &[])
+        };
+
+        assert!(policy.knows("admin"));
+        assert!(!policy.knows("other"));
+        assert!(policy.apply("client", &request("")).is_ok());
+        assert!(policy.apply("client", &request("nobody")).is_ok());
+        assert!(policy.apply("client", &request("65534")).is_ok());
+        assert!(policy.apply("client", &request("daemon")).is_ok());
+        assert!(policy.apply("admin", &request("root")).is_ok());
+        assert!(
+            policy
+                .apply("client", &request("root"))
+                .err()
+                .unwrap()
+                .errors
+                .unwrap()
+                == run_error::Errors::RunError(run_error::Error::UserNotAllowedError as i32)
+        );
+        assert!(policy.apply("other", &request("nobody")).is_err());
+    }
+
+    #[test]
+    fn users_policy_runs_as_the_default_user_and_checks_the_groups() {
+        let request = |group: &str, supplementary_groups: &[&str]| RunRequest {
+            group: group.to_string(),
+            supplementary_groups: supplementary_groups
+                .iter()
+                .map(|group| group.to_string())
+                .collect(),
+            ..request("date", &[])
+        };
+
+        let server_user = UsersPolicy::new(Vec::new(), None).unwrap();
+
+        for denied in &[request("root", &[]), request("", &["disk"])] {
+            assert!(
+                server_user
+                    .apply("client", denied)
+                    .err()
+                    .unwrap()
+                    .errors
+                    .unwrap()
+                    == run_error::Errors::RunError(run_error::Error::GroupNotAllowedError as i32)
+            );
+        }
+
+        assert!(server_user
+            .apply("client", &request("", &[]))
+            .unwrap()
+            .user
+            .is_empty());
+
+        let default_user = UsersPolicy::new(Vec::new(), Some("nobody")).unwrap();
+        let applied = default_user.apply("client", &request("root", &[])).unwrap();
+
+        // the groups of the default user get checked along with the process
+        assert!(applied.user == "nobody");
+        assert!(users::credentials(&applied).is_err());
+        assert!(default_user
+            .apply(
+                "client",
+                &RunRequest {
+                    user: "65534".to_string(),
+                    ..request("", &[])
+                }
+            )
+            .is_ok());
+        assert!(UsersPolicy::new(Vec::new(), Some("no-such-user")).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn env_and_working_dir_get_applied() {
+        let runner = Runner::default();
+
+        let id = runner
+            .run(&RunRequest {
+                env: vec![("GREETING".to_string(), "hello".to_string())]
+                    .into_iter()
+                    .collect(),
+                clear_env: true,
+                working_dir: "/".to_string(),
+                ..request(
+                    "/bin/bash",
+                    &["-c", "echo $GREETING; echo ${HOME:-none}; pwd"],
+                )
+            })
+            .await
+            .unwrap();
+
+        assert!(read_output(&runner, &id).await == "hello\nnone\n/\n".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn status_after_proper_long_run_works() {
+        let runner = Runner::default();
+
+        let run_request = request("sleep", &["60"]);
+
+        let id = runner.run(&run_request).await.unwrap();
+
+        let status_request = StatusRequest { id: id.to_string() };
+
+        let response = runner.status(&status_request).await.unwrap();
+
+        assert!(response.finish.is_none());
+    }
+
+    #[tokio::test]
+    async fn stats_of_unconstrained_process_returns_error() {
+        let runner = Runner::default();
+
+        let id = runner.run(&request("sleep", &["60"])).await.unwrap();
+
+        let unaccounted = runner
+            .stats(&StatsRequest {
+                id: id.to_string(),
+                ..Default::default()
+            })
+            .await;
+
+        let unknown = runner
+            .stats_follow(&StatsRequest {
+                id: Uuid::new_v4().to_string(),
+                ..Default::default()
+            })
+            .await;
+
+        assert!(
+            unaccounted.err().unwrap().errors.unwrap()
+                == stats_error::Errors::StatsError(stats_error::Error::NotAccountedError as i32)
+        );
+        assert!(
+            unknown.err().unwrap().errors.unwrap()
+                == stats_error::Errors::StatsError(stats_error::Error::ProcessNotFoundError as i32)
+        );
+    }
+
+    #[tokio::test]
+    async fn pausing_unconstrained_or_stopped_process_returns_error() {
+        let runner = Runner::default();
+
+        let running = runner.run(&request("sleep", &["60"])).await.unwrap();
+
+        let stopped = runner.run(&request("date", &[])).await.unwrap();
+
+        wait(&runner, &stopped).await;
+
+        let pause = |id: &Uuid| PauseRequest { id: id.to_string() };
+        let resume = |id: &Uuid| ResumeRequest { id: id.to_string() };
+
+        assert!(
+            runner
+                .pause(&pause(&running))
+                .await
+                .err()
+                .unwrap()
+                .errors
+                .unwrap()
+                == pause_error::Errors::PauseError(pause_error::Error::NotConstrainedError as i32)
+        );
+        assert!(
+            runner
+                .pause(&pause(&stopped))
+                .await
+                .err()
+                .unwrap()
+                .errors
+                .unwrap()
+                == pause_error::Errors::PauseError(
+                    pause_error::Error::ProcessAlreadyStoppedError as i32
+                )
+        );
+        assert!(
+            runner
+                .resume(&resume(&running))
+                .await
+                .err()
+                .unwrap()
+                .errors
+                .unwrap()
+                == resume_error::Errors::ResumeError(
+                    resume_error::Error::ProcessNotPausedError as i32
+                )
+        );
+        assert!(
+            runner
+                .resume(&ResumeRequest {
+                    id: "not-an-id".to_string()
+                })
+                .await
+                .err()
+                .unwrap()
+                .errors
+                .unwrap()
+                == resume_error::Errors::ResumeError(resume_error::Error::InvalidId as i32)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn basic_stop_works() {
+        let runner = Runner::default();
+
+        let run_request = request(
+            "/usr/bin/env",
+            &[
+                "bash",
+                "-c",
+                "for i in $(seq 1 10000000000000); do echo $i; done",
+            ],
+        );
+
+        let id = runner.run(&run_request).await.unwrap();
+        let pid = runner.pid_for_process(&id).await.unwrap();
+
+        let mut system = sysinfo::System::new();
+        assert!(system.get_process(pid as i32).is_some());
+
+        let stop_request = StopRequest {
+            id: id.to_string(),
+            ..Default::default()
+        };
+        let resp = runner.stop(&stop_request).await;
+
+        resp.unwrap();
+
+        system.refresh_processes();
+
+        if let Some(process) = system.get_process(pid as i32) {
+            assert!(
+                process.status.as_ref().unwrap().to_string()
+                    == sysinfo::ProcessStatus::Dead.to_string()
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stop_kills_processes_ignoring_the_signal_after_the_grace_period() {
+        let runner = Runner::default();
+
+        let id = runner
+            .run(&request("bash", &["-c", "trap '' INT; sleep 60"]))
+            .await
+            .unwrap();
+
+        // gives bash the time to set up the trap
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let start = Instant::now();
+
+        runner
+            .stop(&StopRequest {
+                id: id.to_string(),
+                signal: signal::Signal::SIGINT as i32,
+                grace_period: Some(stop_request::GracePeriod::GracePeriodSeconds(1)),
+            })
+            .await
+            .unwrap();
+
+        let status = wait(&runner, &id).await;
+
+        assert!(start.elapsed() < DEFAULT_GRACE_PERIOD);
+        assert!(
+            status.finish
+                == Some(status_result::Finish::Result(status_result::ExitResult {
+                    kill: Some(status_result::exit_result::Kill::Signal(9)),
+                    ..Default::default()
+                }))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn process_gets_stopped_once_it_runs_for_longer_than_its_timeout() {
+        let runner = Runner::default();
+
+        let timed_out = runner
+            .run(&RunRequest {
+                timeout: Some(run_request::Timeout::TimeoutSeconds(1)),
+                ..request("sleep", &["60"])
+            })
+            .await
+            .unwrap();
+
+        let finished = runner
+            .run(&RunRequest {
+                timeout: Some(run_request::Timeout::TimeoutSeconds(60)),
+                ..request("true", &[])
+            })
+            .await
+            .unwrap();
+
+        let start = Instant::now();
+        let mut statuses = Vec::new();
+
+        for id in &[timed_out, finished] {
+            let status = wait(&runner, id).await;
+
+            statuses.push(status.finish);
+        }
+
+        assert!(start.elapsed() < DEFAULT_GRACE_PERIOD);
+        assert!(
+            statuses[0]
+                == Some(status_result::Finish::Result(status_result::ExitResult {
+                    kill: Some(status_result::exit_result::Kill::Signal(15)),
+                    reason: status_result::exit_result::Reason::TimedOut as i32,
+                    ..Default::default()
+                }))
+        );
+        assert!(
+            statuses[1]
+                == Some(status_result::Finish::Result(status_result::ExitResult {
+                    exit: Some(status_result::exit_result::Exit::Code(0)),
+                    ..Default::default()
+                }))
+        );
+    }
+
+    #[tokio::test]
+    async fn zero_timeout_is_rejected() {
+        let runner = Runner::default();
+
+        let res = runner
+            .run(&RunRequest {
+                timeout: Some(run_request::Timeout::TimeoutSeconds(0)),
+                ..request("sleep", &["60"])
+            })
+            .await;
+
+        assert!(
+            res.err().unwrap().errors.unwrap()
+                == run_error::Errors::RunError(run_error::Error::InvalidTimeoutError as i32)
+        );
+        assert!(runner.processes.read().await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stop_terminates_the_descendants_too() {
+        let runner = Runner::default();
+
+        let id = runner
+            .run(&request("bash", &["-c", "sleep 60 & echo $!; wait"]))
+            .await
+            .unwrap();
+
+        let mut output = Vec::new();
+
+        while output.is_empty() {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            output = runner
+                .log(&LogRequest {
+                    id: id.to_string(),
+                    ..Default::default()
+                })
+                .await
+                .unwrap()
+                .map(|chunk| chunk.unwrap().data)
+                .concat()
+                .await;
+        }
+
+        let descendant = std::str::from_utf8(&output)
+            .unwrap()
+            .trim()
+            .parse::<i32>()
+            .unwrap();
+
+        let start = Instant::now();
+
+        runner
+            .stop(&StopRequest {
+                id: id.to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert!(start.elapsed() < DEFAULT_GRACE_PERIOD);
+        assert!(signal::kill(Pid::from_raw(descendant), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn signalling_delivers_given_signal() {
+        let runner = Runner::default();
+
+        let id = runner.run(&request("sleep", &["60"])).await.unwrap();
+
+        let invalid = runner
+            .signal(&SignalRequest {
+                id: id.to_string(),
+                signal: 0,
+                group: false,
+            })
+            .await;
+
+        runner
+            .signal(&SignalRequest {
+                id: id.to_string(),
+                signal: signal::Signal::SIGUSR1 as i32,
+                group: false,
+            })
+            .await
+            .unwrap();
+
+        let status = wait(&runner, &id).await;
+
+        assert!(
+            invalid.err().unwrap().errors.unwrap()
+                == signal_error::Errors::SignalError(
+                    signal_error::Error::InvalidSignalError as i32
+                )
+        );
+        assert!(
+            status.finish
+                == Some(status_result::Finish::Result(status_result::ExitResult {
+                    kill: Some(status_result::exit_result::Kill::Signal(
+                        signal::Signal::SIGUSR1 as i32
+                    )),
+                    ..Default::default()
+                }))
+        );
+    }
+
+    #[tokio::test]
+    async fn gathering_logs_via_log_request_and_responce_stream_works_asynchronously() {
+        let runner = Runner::default();
+
+        let run_request = request(
+            "/usr/bin/env",
+            &["bash", "-c", "for i in $(seq 1 4); do echo $i; done"],
+        );
+
+        let id = runner.run(&run_request).await.unwrap();
+
+        // there's no need to wait for logs here since the
+        // following log request is an async stream of values anyway
+        // and it does its own waiting
+        // (previous version had a thread::sleep here which was a remnant
+        // of dev+debug time - it wasn't intended to stay here)
+
+        let log_request = LogRequest {
+            id: id.to_string(),
+            descriptor: log_request::Descriptor::Stdout as i32,
+            follow: true,
+            ..Default::default()
+        };
+
+        // the chunks are streamed as soon as they're captured, so the
+        // output can come in more than one of them
+        let data = runner
+            .log(&log_request)
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap().data)
+            .concat()
+            .await;
+
+        assert!(data == "1\n2\n3\n4\n".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn closed_processes_have_streams_with_an_end() {
+        let runner = Runner::default();
+
+        // let's use /usr/bin/env here not to assume where echo resides
+        // env is most often under /usr/bin
+        let run_request = request("/usr/bin/env", &["bash", "-c", "echo test"]);
+
+        let id = runner.run(&run_request).await.unwrap();
+
+        let log_request = LogRequest {
+            id: id.to_string(),
+            descriptor: log_request::Descriptor::Stdout as i32,
+            follow: true,
+            ..Default::default()
+        };
+
+        let mut stream = runner.log(&log_request).await.unwrap();
+        let first_value = stream.next().await;
+
+        assert!(first_value.unwrap().unwrap().data == "test\n".as_bytes());
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn both_descriptors_are_streamed_in_the_order_of_the_output() {
+        let runner = Runner::default();
+
+        let run_request = request(
+            "/usr/bin/env",
+            &[
+                "bash",
+                "-c",
+                "echo 1; sleep 0.1; echo 2 >&2; sleep 0.1; echo 3",
+            ],
+        );
+
+        let id = runner.run(&run_request).await.unwrap();
+
+        let log_request = LogRequest {
+            id: id.to_string(),
+            descriptor: log_request::Descriptor::Both as i32,
+            follow: true,
+            ..Default::default()
+        };
+
+        let chunks = runner
+            .log(&log_request)
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect::<Vec<_>>()
+            .await;
+
+        let output = chunks
+            .iter()
+            .map(|chunk| (chunk.descriptor, chunk.data.clone()))
+            .collect::<Vec<_>>();
+
+        assert!(
+            output
+                == vec![
+                    (log_request::Descriptor::Stdout as i32, b"1\n".to_vec()),
+                    (log_request::Descriptor::Stderr as i32, b"2\n".to_vec()),
+                    (log_request::Descriptor::Stdout as i32, b"3\n".to_vec()),
+                ]
+        );
+        assert!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.sequence)
+                .collect::<Vec<_>>()
+                == vec![0, 1, 2]
+        );
+    }
+
+    #[tokio::test]
+    async fn logs_can_be_read_from_an_offset_or_with_the_last_lines() {
+        let runner = Runner::default();
+
+        let run_request = request(
+            "/usr/bin/env",
+            &[
+                "bash",
+                "-c",
+                "echo 1; sleep 0.1; echo 2 >&2; sleep 0.1; echo 3",
+            ],
+        );
+
+        let id = runner.run(&run_request).await.unwrap();
+
+        // the followed stream ends once all of the output is captured
+        runner
+            .log(&LogRequest {
+                id: id.to_string(),
+                follow: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+
+        let read = |descriptor: log_request::Descriptor, start_offset: u64, tail_lines: u32| {
+            let runner = runner.clone();
+            let log_request = LogRequest {
+                id: id.to_string(),
+                descriptor: descriptor as i32,
+                start_offset,
+                tail_lines,
+                follow: false,
+            };
+
+            async move {
+                runner
+                    .log(&log_request)
+                    .await
+                    .unwrap()
+                    .map(|chunk| {
+                        let chunk = chunk.unwrap();
+                        (chunk.descriptor, chunk.offset, chunk.data)
+                    })
+                    .collect::<Vec<_>>()
+                    .await
+            }
+        };
+
+        let stdout = log_request::Descriptor::Stdout as i32;
+        let stderr = log_request::Descriptor::Stderr as i32;
+
+        assert!(
+            read(log_request::Descriptor::Both, 0, 2).await
+                == vec![(stderr, 0, b"2\n".to_vec()), (stdout, 2, b"3\n".to_vec())]
+        );
+        assert!(
+            read(log_request::Descriptor::Stdout, 0, 1).await == vec![(stdout, 2, b"3\n".to_vec())]
+        );
+        assert!(
+            read(log_request::Descriptor::Stdout, 1, 0).await
+                == vec![(stdout, 1, b"\n3\n".to_vec())]
+        );
+        assert!(read(log_request::Descriptor::Stdout, 0, 10).await.len() == 1);
+
+        let error = runner
+            .log(&LogRequest {
+                id: id.to_string(),
+                descriptor: log_request::Descriptor::Both as i32,
+                start_offset: 1,
+                ..Default::default()
+            })
+            .await
+            .err()
+            .unwrap();
+
+        assert!(
+            error.errors
+                == Some(log_error::Errors::LogError(
+                    log_error::Error::InvalidRange as i32
+                ))
+        );
+    }
+
+    /// Returns the whole output of a finished process kept on disk
+    async fn read_output(runner: &Runner, id: &Uuid) -> Vec<u8> {
+        runner
+            .log(&LogRequest {
+                id: id.to_string(),
+                follow: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap().data)
+            .concat()
+            .await
+    }
+
+    /// Builds the request to run a command with given arguments
+    fn request(command: &str, arguments: &[&str]) -> RunRequest {
+        RunRequest {
+            command: command.to_string(),
+            arguments: arguments
+                .iter()
+                .map(|argument| argument.to_string())
+                .collect(),
+            ..Default::default()
         }
     }
 
-    /// Returns the path to stdout file for a process
-    fn stdout_path(&self, id: &Uuid) -> PathBuf {
-        let mut path = PathBuf::new();
+    /// Waits until a process finishes and returns its status
+    async fn wait(runner: &Runner, id: &Uuid) -> StatusResult {
+        runner
+            .wait(&WaitRequest {
+                id: id.to_string(),
+                timeout: None,
+            })
+            .await
+            .unwrap()
+    }
 
-        path.push(&self.log_dir);
-        path.push(format!("{}.stdout.txt", id));
+    /// Waits until the output of a process is fully captured
+    async fn wait_for_capture(runner: &Runner, id: &Uuid) {
+        let output = runner.processes.read().await[id].output.clone();
 
-        path
+        if let Some(mut output) = output {
+            while output.changed().await.is_ok() {}
+        }
     }
 
-    /// Returns the path to stderr file for a process
-    fn stderr_path(&self, id: &Uuid) -> PathBuf {
-        let mut path = PathBuf::new();
+    /// Runs a process until it finishes and its output is fully captured
+    async fn run_to_completion(runner: &Runner, request: &RunRequest) -> Uuid {
+        let id = runner.run(request).await.unwrap();
 
-        path.push(&self.log_dir);
-        path.push(format!("{}.stderr.txt", id));
+        wait_for_capture(runner, &id).await;
+        wait(runner, &id).await;
 
-        path
+        id
     }
 
-    /// Returns the PID for a given UUID id of the process
-    async fn pid_for_process(&self, id: &Uuid) -> Option<u32> {
-        let processes = self.processes.read().await;
+    /// Returns the path of a journal of its own for a test
+    fn journal_path() -> PathBuf {
+        PathBuf::from(format!("tmp/{}.journal", Uuid::new_v4()))
+    }
 
-        if let Some((pid, _)) = (*processes).get(id) {
-            Some(*pid)
-        } else {
-            None
-        }
+    /// Opens a runner persisting its processes in a given journal
+    async fn open_runner(journal_path: &Path) -> Runner {
+        Runner::open(
+            "tmp".to_string(),
+            journal_path,
+            Quotas::default(),
+            Retention::default(),
+        )
+        .await
+        .unwrap()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    extern crate sysinfo;
-    extern crate tokio;
+    /// Records a process as started by a previous instance of the server
+    async fn journal_started(journal_path: &Path, pid: u32, request: RunRequest) -> Uuid {
+        let id = Uuid::new_v4();
+        let (journal, _) = Journal::open(journal_path).await.unwrap();
+
+        journal
+            .started(
+                &id,
+                &Process {
+                    pid,
+                    start_time: procfs::start_time(pid),
+                    status: Running,
+                    request,
+                    started_at: SystemTime::now(),
+                    finished_at: None,
+                    accounting: Accounting::default(),
+                    termination: None,
+                    output: None,
+                    segments: Segments::default(),
+                    stdin: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        id
+    }
 
-    use super::*;
-    use futures::StreamExt;
-    use sysinfo::SystemExt;
+    #[tokio::test]
+    async fn output_going_over_the_quota_gets_its_head_truncated() {
+        let runner = Runner::default();
+
+        let id = runner
+            .run(&RunRequest {
+                log_quota: Some(run_request::LogQuota::MaxLogSize(16384)),
+                log_policy: run_request::LogPolicy::TruncateHead as i32,
+                ..request("seq", &["1", "20000"])
+            })
+            .await
+            .unwrap();
+
+        // a follower keeping up with the output would read its head
+        // before it gets truncated
+        wait_for_capture(&runner, &id).await;
+
+        let output = read_output(&runner, &id).await;
+
+        assert!(output.len() < 16384);
+        assert!(output.ends_with(b"19999\n20000\n"));
+        assert!(!output.starts_with(b"1\n2\n"));
+        assert!(runner.log_usage.load(Ordering::SeqCst) <= 16384);
+    }
 
     #[tokio::test]
-    async fn proper_run_returns_correct_uuid() {
+    async fn chunks_bigger_than_the_quota_get_cut_to_fit() {
         let runner = Runner {
             log_dir: "tmp".to_string(),
+            buffer_size: Some(4096),
             ..Default::default()
         };
 
-        let request = RunRequest {
-            command: "date".to_string(),
-            ..Default::default()
-        };
+        let id = runner
+            .run(&RunRequest {
+                log_quota: Some(run_request::LogQuota::MaxLogSize(1000)),
+                log_policy: run_request::LogPolicy::TruncateHead as i32,
+                ..request("seq", &["1", "20000"])
+            })
+            .await
+            .unwrap();
+
+        wait_for_capture(&runner, &id).await;
+
+        let output = read_output(&runner, &id).await;
+        let on_disk = std::fs::read_dir("tmp")
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(&id.to_string())
+            })
+            .map(|entry| entry.metadata().unwrap().len())
+            .sum::<u64>();
+
+        assert!(output.ends_with(b"19999\n20000\n"));
+        assert!(on_disk <= 1000);
+        assert!(runner.log_usage.load(Ordering::SeqCst) == on_disk);
+    }
 
-        runner.run(&request).await.unwrap();
+    #[tokio::test]
+    async fn rotated_output_is_read_across_its_segments() {
+        let runner = Runner::default();
+
+        let id = runner
+            .run(&RunRequest {
+                log_quota: Some(run_request::LogQuota::MaxLogSize(100000)),
+                log_policy: run_request::LogPolicy::Rotate as i32,
+                log_segments: 50,
+                ..request("seq", &["1", "2000"])
+            })
+            .await
+            .unwrap();
+
+        let output = read_output(&runner, &id).await;
+        let expected = (1..=2000).map(|i| format!("{}\n", i)).collect::<String>();
+
+        assert!(output == expected.as_bytes());
+        assert!(runner.processes.read().await[&id].segments.numbers().len() > 1);
     }
 
     #[tokio::test]
-    async fn incorrect_run_returns_error() {
+    async fn processes_going_over_the_quota_get_killed_with_the_kill_policy() {
+        let runner = Runner::default();
+
+        let id = runner
+            .run(&RunRequest {
+                log_quota: Some(run_request::LogQuota::MaxLogSize(4096)),
+                log_policy: run_request::LogPolicy::Kill as i32,
+                ..request("sh", &["-c", "yes & yes"])
+            })
+            .await
+            .unwrap();
+
+        let result = runner
+            .wait(&WaitRequest {
+                id: id.to_string(),
+                timeout: Some(wait_request::Timeout::TimeoutSeconds(5)),
+            })
+            .await
+            .unwrap();
+
+        assert!(
+            result.finish
+                == Some(status_result::Finish::Result(status_result::ExitResult {
+                    exit: None,
+                    kill: Some(status_result::exit_result::Kill::Signal(9)),
+                    lost: false,
+                    reason: status_result::exit_result::Reason::LogQuotaExceeded as i32,
+                    ..Default::default()
+                }))
+        );
+
+        // the capture only ends once the descendants are gone too
+        let captured =
+            tokio::time::timeout(Duration::from_secs(5), wait_for_capture(&runner, &id)).await;
+
+        assert!(captured.is_ok());
+        assert!(read_output(&runner, &id).await.len() <= 4096);
+    }
+
+    #[tokio::test]
+    async fn output_going_over_the_total_quota_is_dropped() {
         let runner = Runner {
-            log_dir: "tmp".to_string(),
+            quotas: Quotas {
+                job: None,
+                total: Some(8192),
+            },
             ..Default::default()
         };
 
-        let request = RunRequest {
-            command: "".to_string(),
-            ..Default::default()
-        };
+        let id = runner.run(&request("seq", &["1", "20000"])).await.unwrap();
 
-        let res = runner.run(&request).await;
+        let output = read_output(&runner, &id).await;
+
+        assert!(output.starts_with(b"1\n2\n"));
+        assert!(output.len() < 8192);
+        assert!(runner.log_usage.load(Ordering::SeqCst) <= 8192);
+    }
+
+    #[tokio::test]
+    async fn deleting_removes_finished_processes_along_with_their_logs() {
+        let runner = Runner::default();
+
+        let finished = run_to_completion(&runner, &request("seq", &["1", "100"])).await;
+        let running = runner.run(&request("sleep", &["60"])).await.unwrap();
+
+        let usage = runner.log_usage.load(Ordering::SeqCst);
+        let segments = runner.processes.read().await[&finished].segments.clone();
+
+        runner
+            .delete(&DeleteRequest {
+                id: finished.to_string(),
+            })
+            .await
+            .unwrap();
+
+        let status = runner
+            .status(&StatusRequest {
+                id: finished.to_string(),
+            })
+            .await;
 
-        assert!(res.is_err());
         assert!(
-            res.err().unwrap().errors.unwrap()
-                == service::run_response::run_error::Errors::RunError(
-                    service::run_response::run_error::Error::NameEmptyError as i32
-                )
+            status.err().unwrap().errors
+                == Some(status_error::Errors::StatusError(
+                    status_error::Error::ProcessNotFoundError as i32
+                ))
+        );
+        assert!(segments.numbers().is_empty());
+        assert!(!Path::new(&format!("tmp/{}.0.stdout.txt", finished)).exists());
+        assert!(runner.log_usage.load(Ordering::SeqCst) < usage);
+
+        let res = runner
+            .delete(&DeleteRequest {
+                id: running.to_string(),
+            })
+            .await;
+
+        assert!(
+            res.err().unwrap().errors
+                == Some(delete_error::Errors::DeleteError(
+                    delete_error::Error::ProcessRunningError as i32
+                ))
         );
     }
 
     #[tokio::test]
-    async fn status_after_proper_long_run_works() {
+    async fn collecting_deletes_the_oldest_finished_processes_over_the_retention() {
         let runner = Runner {
-            log_dir: "tmp".to_string(),
+            retention: Retention {
+                max_jobs: Some(1),
+                ..Default::default()
+            },
             ..Default::default()
         };
 
-        let run_request = RunRequest {
-            command: "sleep".to_string(),
-            arguments: vec!["60".to_string()],
-            ..Default::default()
-        };
+        let mut ids = Vec::new();
 
-        let id = runner.run(&run_request).await.unwrap();
+        for _ in 0..2 {
+            ids.push(run_to_completion(&runner, &request("date", &[])).await);
+        }
 
-        let status_request = StatusRequest { id: id.to_string() };
+        assert!(runner.collect().await == 1);
+        assert!(runner
+            .status(&StatusRequest {
+                id: ids[0].to_string()
+            })
+            .await
+            .is_err());
+        assert!(runner
+            .status(&StatusRequest {
+                id: ids[1].to_string()
+            })
+            .await
+            .is_ok());
+        assert!(runner.collect().await == 0);
+    }
 
-        let response = runner.status(&status_request).await.unwrap();
+    #[tokio::test]
+    async fn finished_processes_are_loaded_back_from_the_journal() {
+        let journal_path = journal_path();
+        let runner = open_runner(&journal_path).await;
 
-        assert!(response.finish.is_none());
+        let id = run_to_completion(&runner, &request("date", &[])).await;
+
+        let response = open_runner(&journal_path)
+            .await
+            .status(&StatusRequest { id: id.to_string() })
+            .await
+            .unwrap();
+
+        std::fs::remove_file(&journal_path).unwrap();
+
+        assert!(
+            response.finish
+                == Some(status_result::Finish::Result(status_result::ExitResult {
+                    exit: Some(status_result::exit_result::Exit::Code(0)),
+                    kill: None,
+                    lost: false,
+                    ..Default::default()
+                }))
+        );
     }
 
-    #[tokio::test(flavor = "multi_thread")]
-    async fn basic_stop_works() {
-        let runner = Runner {
-            log_dir: "tmp".to_string(),
-            ..Default::default()
+    #[tokio::test]
+    async fn env_and_stdin_arent_kept_in_the_journal() {
+        let journal_path = journal_path();
+        let runner = open_runner(&journal_path).await;
+
+        run_to_completion(
+            &runner,
+            &RunRequest {
+                env: vec![("TOKEN".to_string(), "env-secret".to_string())]
+                    .into_iter()
+                    .collect(),
+                stdin: b"stdin-secret".to_vec(),
+                ..request("cat", &[])
+            },
+        )
+        .await;
+
+        let journal = std::fs::read(&journal_path).unwrap();
+        std::fs::remove_file(&journal_path).unwrap();
+
+        let contains = |secret: &[u8]| journal.windows(secret.len()).any(|window| window == secret);
+
+        assert!(contains(b"cat"));
+        assert!(!contains(b"env-secret"));
+        assert!(!contains(b"stdin-secret"));
+    }
+
+    #[tokio::test]
+    async fn timed_out_processes_are_loaded_back_with_their_reason() {
+        let journal_path = journal_path();
+        let runner = open_runner(&journal_path).await;
+
+        let id = run_to_completion(
+            &runner,
+            &RunRequest {
+                timeout: Some(run_request::Timeout::TimeoutSeconds(1)),
+                ..request("sleep", &["60"])
+            },
+        )
+        .await;
+
+        let response = open_runner(&journal_path)
+            .await
+            .status(&StatusRequest { id: id.to_string() })
+            .await
+            .unwrap();
+
+        std::fs::remove_file(&journal_path).unwrap();
+
+        assert!(
+            response.finish
+                == Some(status_result::Finish::Result(status_result::ExitResult {
+                    kill: Some(status_result::exit_result::Kill::Signal(15)),
+                    reason: status_result::exit_result::Reason::TimedOut as i32,
+                    ..Default::default()
+                }))
+        );
+    }
+
+    #[tokio::test]
+    async fn deleted_processes_arent_loaded_back_from_the_journal() {
+        let journal_path = journal_path();
+        let runner = open_runner(&journal_path).await;
+
+        let deleted = run_to_completion(&runner, &request("date", &[])).await;
+        let kept = run_to_completion(&runner, &request("date", &[])).await;
+
+        runner
+            .delete(&DeleteRequest {
+                id: deleted.to_string(),
+            })
+            .await
+            .unwrap();
+
+        let size = std::fs::metadata(&journal_path).unwrap().len();
+        let reopened = open_runner(&journal_path).await;
+
+        // the entries of the deleted process are compacted away
+        let compacted_size = std::fs::metadata(&journal_path).unwrap().len();
+
+        std::fs::remove_file(&journal_path).unwrap();
+        runner
+            .delete(&DeleteRequest {
+                id: kept.to_string(),
+            })
+            .await
+            .unwrap();
+
+        let status = |id: Uuid| {
+            let reopened = reopened.clone();
+            async move { reopened.status(&StatusRequest { id: id.to_string() }).await }
         };
 
-        let run_request = RunRequest {
-            command: "/usr/bin/env".to_string(),
-            arguments: vec![
-                "bash".to_string(),
-                "-c".to_string(),
-                "for i in $(seq 1 10000000000000); do echo $i; done".to_string(),
-            ],
-            ..Default::default()
+        assert!(status(deleted).await.is_err());
+        assert!(status(kept).await.unwrap().finish.is_some());
+        assert!(compacted_size < size / 2);
+    }
+
+    #[tokio::test]
+    async fn only_a_cut_short_entry_at_the_end_of_the_journal_is_discarded() {
+        let journal_path = journal_path();
+        let runner = open_runner(&journal_path).await;
+
+        let id = run_to_completion(&runner, &request("date", &[])).await;
+        let journal = std::fs::read(&journal_path).unwrap();
+
+        // the beginning of an entry whose end never made it to the file
+        let mut cut_short = journal.clone();
+        cut_short.extend_from_slice(&journal[..3]);
+        std::fs::write(&journal_path, &cut_short).unwrap();
+
+        let reopened = open_runner(&journal_path).await;
+        let truncated = std::fs::read(&journal_path).unwrap();
+
+        // a broken entry followed by valid ones
+        let mut corrupted = journal.clone();
+        let length_size = if journal[0] & 0x80 != 0 { 2 } else { 1 };
+        corrupted[length_size] = 0xff;
+        std::fs::write(&journal_path, &corrupted).unwrap();
+
+        let refused = Runner::open(
+            "tmp".to_string(),
+            &journal_path,
+            Quotas::default(),
+            Retention::default(),
+        )
+        .await;
+        let kept = std::fs::read(&journal_path).unwrap();
+
+        std::fs::remove_file(&journal_path).unwrap();
+        runner
+            .delete(&DeleteRequest { id: id.to_string() })
+            .await
+            .unwrap();
+
+        assert!(reopened
+            .status(&StatusRequest { id: id.to_string() })
+            .await
+            .is_ok());
+        assert!(truncated == journal);
+        assert!(refused.is_err());
+        assert!(kept == corrupted);
+    }
+
+    #[tokio::test]
+    async fn processes_left_running_are_adopted_until_they_exit() {
+        let journal_path = journal_path();
+        let mut child = std::process::Command::new("sleep")
+            .arg("60")
+            .spawn()
+            .unwrap();
+
+        let id = journal_started(&journal_path, child.id(), request("sleep", &["60"])).await;
+        let runner = open_runner(&journal_path).await;
+        let status_request = StatusRequest { id: id.to_string() };
+
+        assert!(runner
+            .status(&status_request)
+            .await
+            .unwrap()
+            .finish
+            .is_none());
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let response = loop {
+            let response = runner.status(&status_request).await.unwrap();
+
+            if response.finish.is_some() {
+                break response;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
         };
 
-        let id = runner.run(&run_request).await.unwrap();
-        let pid = runner.pid_for_process(&id).await.unwrap();
+        std::fs::remove_file(&journal_path).unwrap();
 
-        let mut system = sysinfo::System::new();
-        assert!(system.get_process(pid as i32).is_some());
+        assert!(
+            response.finish
+                == Some(status_result::Finish::Result(status_result::ExitResult {
+                    exit: None,
+                    kill: None,
+                    lost: true,
+                    ..Default::default()
+                }))
+        );
+    }
 
-        let stop_request = StopRequest { id: id.to_string() };
-        let resp = runner.stop(&stop_request).await;
+    #[tokio::test]
+    async fn processes_gone_while_the_server_was_down_are_lost() {
+        let journal_path = journal_path();
+        let mut child = std::process::Command::new("sleep")
+            .arg("60")
+            .spawn()
+            .unwrap();
 
-        resp.unwrap();
+        let id = journal_started(&journal_path, child.id(), request("sleep", &["60"])).await;
 
-        system.refresh_processes();
+        child.kill().unwrap();
+        child.wait().unwrap();
 
-        if let Some(process) = system.get_process(pid as i32) {
-            assert!(
-                process.status.as_ref().unwrap().to_string()
-                    == sysinfo::ProcessStatus::Dead.to_string()
-            );
-        }
+        let response = open_runner(&journal_path)
+            .await
+            .status(&StatusRequest { id: id.to_string() })
+            .await
+            .unwrap();
+
+        std::fs::remove_file(&journal_path).unwrap();
+
+        assert!(
+            response.finish
+                == Some(status_result::Finish::Result(status_result::ExitResult {
+                    exit: None,
+                    kill: None,
+                    lost: true,
+                    ..Default::default()
+                }))
+        );
     }
 
     #[tokio::test]
-    async fn gathering_logs_via_log_request_and_responce_stream_works_asynchronously() {
-        let runner = Runner {
-            log_dir: "tmp".to_string(),
-            ..Default::default()
-        };
+    async fn listing_filters_and_paginates_processes() {
+        let runner = Runner::default();
 
-        let run_request = RunRequest {
-            command: "/usr/bin/env".to_string(),
-            arguments: vec![
-                "bash".to_string(),
-                "-c".to_string(),
-                "for i in $(seq 1 4); do echo $i; done".to_string(),
-            ],
+        let sleep_request = request("sleep", &["60"]);
+
+        let first = runner.run(&sleep_request).await.unwrap();
+        let second = runner.run(&sleep_request).await.unwrap();
+
+        let date = run_to_completion(&runner, &request("date", &[])).await;
+
+        let list_request = ListRequest {
+            command_prefix: "sl".to_string(),
+            page_size: 1,
             ..Default::default()
         };
 
-        let id = runner.run(&run_request).await.unwrap();
+        let (page, page_token) = runner.list(&list_request).await.unwrap();
 
-        // there's no need to wait for logs here since the
-        // following log request is an async stream of values anyway
-        // and it does its own waiting
-        // (previous version had a thread::sleep here which was a remnant
-        // of dev+debug time - it wasn't intended to stay here)
+        assert!(page.len() == 1);
+        assert!(!page_token.is_empty());
 
-        let log_request = LogRequest {
-            id: id.to_string(),
-            descriptor: log_request::Descriptor::Stdout as i32,
-        };
+        let (next_page, page_token) = runner
+            .list(&ListRequest {
+                page_token,
+                ..list_request
+            })
+            .await
+            .unwrap();
 
-        let mut stream = runner.log(&log_request).await.unwrap();
-        let first_value = stream.next().await;
+        assert!(next_page.len() == 1);
+        assert!(page_token.is_empty());
+
+        let mut ids = vec![page[0].id.clone(), next_page[0].id.clone()];
+        ids.sort();
+
+        let mut expected = vec![first.to_string(), second.to_string()];
+        expected.sort();
 
-        assert!(first_value.unwrap().unwrap() == "1\n2\n3\n4\n".as_bytes());
+        assert!(ids == expected);
+
+        let (stopped, _) = runner
+            .list(&ListRequest {
+                state: list_request::State::Stopped as i32,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert!(stopped.len() == 1);
+        assert!(stopped[0].id == date.to_string());
+        assert!(stopped[0].finish.is_some());
     }
 
     #[tokio::test]
-    async fn closed_processes_have_streams_with_an_end() {
-        let runner = Runner {
-            log_dir: "tmp".to_string(),
-            ..Default::default()
-        };
+    async fn listing_with_invalid_page_token_returns_error() {
+        let runner = Runner::default();
 
-        // let's use /usr/bin/env here not to assume where echo resides
-        // env is most often under /usr/bin
-        let run_request = RunRequest {
-            command: "/usr/bin/env".to_string(),
-            arguments: vec![
-                "bash".to_string(),
-                "-c".to_string(),
-                "echo test".to_string(),
-            ],
-            ..Default::default()
-        };
+        let res = runner
+            .list(&ListRequest {
+                page_token: "invalid".to_string(),
+                ..Default::default()
+            })
+            .await;
 
-        let id = runner.run(&run_request).await.unwrap();
+        assert!(
+            res.err().unwrap().errors.unwrap()
+                == list_error::Errors::ListError(list_error::Error::InvalidPageToken as i32)
+        );
+    }
 
-        let log_request = LogRequest {
-            id: id.to_string(),
-            descriptor: log_request::Descriptor::Stdout as i32,
+    #[tokio::test(flavor = "multi_thread")]
+    async fn watching_streams_lifecycle_events_of_given_processes() {
+        let runner = Runner::default();
+
+        let other = runner.run(&request("sleep", &["60"])).await.unwrap();
+
+        let stopped = runner.run(&request("sleep", &["60"])).await.unwrap();
+
+        let watch_request = WatchRequest {
+            ids: vec![stopped.to_string()],
         };
 
-        let mut stream = runner.log(&log_request).await.unwrap();
-        let first_value = stream.next().await;
+        let mut stream = runner.watch(&watch_request).await.unwrap();
+
+        runner
+            .stop(&StopRequest {
+                id: other.to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        runner
+            .stop(&StopRequest {
+                id: stopped.to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let first = stream.next().await.unwrap().unwrap();
+        let second = stream.next().await.unwrap().unwrap();
+
+        assert!(first.id == stopped.to_string());
+        assert!(first.kind == Kind::StopRequested as i32);
+        assert!(second.id == stopped.to_string());
+        assert!(second.kind == Kind::Killed as i32);
+        assert!(second.signal == signal::Signal::SIGTERM as i32);
+    }
 
-        assert!(first_value.unwrap().unwrap() == "test\n".as_bytes());
-        assert!(stream.next().await.is_none());
+    #[tokio::test]
+    async fn waiting_returns_the_status_once_the_process_finishes() {
+        let runner = Runner::default();
+
+        let id = runner
+            .run(&request(
+                "/usr/bin/env",
+                &["bash", "-c", "sleep 0.2; exit 3"],
+            ))
+            .await
+            .unwrap();
+
+        let result = wait(&runner, &id).await;
+
+        assert!(
+            result.finish
+                == Some(status_result::Finish::Result(status_result::ExitResult {
+                    exit: Some(status_result::exit_result::Exit::Code(3)),
+                    kill: None,
+                    lost: false,
+                    ..Default::default()
+                }))
+        );
+    }
+
+    #[tokio::test]
+    async fn waiting_with_timeout_gives_up() {
+        let runner = Runner::default();
+
+        let id = runner.run(&request("sleep", &["60"])).await.unwrap();
+
+        let res = runner
+            .wait(&WaitRequest {
+                id: id.to_string(),
+                timeout: Some(wait_request::Timeout::TimeoutSeconds(1)),
+            })
+            .await;
+
+        assert!(
+            res.err().unwrap().errors.unwrap()
+                == wait_error::Errors::WaitError(wait_error::Error::TimeoutError as i32)
+        );
+    }
+
+    #[tokio::test]
+    async fn following_streams_both_descriptors_and_ends_with_status() {
+        let runner = Runner::default();
+
+        let id = runner
+            .run(&request(
+                "/usr/bin/env",
+                &["bash", "-c", "echo out; echo err >&2; exit 2"],
+            ))
+            .await
+            .unwrap();
+
+        let items = runner
+            .follow(&id, 0)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut stdout: Vec<u8> = Vec::new();
+        let mut stderr: Vec<u8> = Vec::new();
+
+        for item in &items[..items.len() - 1] {
+            match item {
+                run_follow_response::Results::Output(output)
+                    if output.descriptor == log_request::Descriptor::Stdout as i32 =>
+                {
+                    stdout.extend(&output.data)
+                }
+                run_follow_response::Results::Output(output) => stderr.extend(&output.data),
+                _ => panic!("unexpected item: {:?}", item),
+            }
+        }
+
+        assert!(stdout == "out\n".as_bytes());
+        assert!(stderr == "err\n".as_bytes());
+        assert!(
+            items.last()
+                == Some(&run_follow_response::Results::Status(StatusResult {
+                    finish: Some(status_result::Finish::Result(status_result::ExitResult {
+                        exit: Some(status_result::exit_result::Exit::Code(2)),
+                        kill: None,
+                        lost: false,
+                        ..Default::default()
+                    })),
+                    pids_limit_hits: 0,
+                    paused: false,
+                }))
+        );
     }
 }
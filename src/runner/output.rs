@@ -0,0 +1,828 @@
+use crate::runner::service::{log_request::Descriptor, run_request::LogPolicy};
+use anyhow::{anyhow, Context, Result};
+use log::warn;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryInto;
+use std::io::{ErrorKind, SeekFrom};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::watch;
+use uuid::Uuid;
+
+use super::journal::to_millis;
+
+/// The size of a single record in the index file
+pub const RECORD_SIZE: usize = 21;
+
+/// The size of the header of the index file
+const HEADER_SIZE: usize = 24;
+
+/// The number of segments the output is split into with the TRUNCATE_HEAD
+/// policy. The more segments, the closer the kept output gets to the quota
+const RING_SEGMENTS: u64 = 16;
+
+/// The number of segments the output is rotated into with the ROTATE policy
+/// unless requested otherwise
+const DEFAULT_ROTATED_SEGMENTS: u64 = 5;
+
+/// The least size of a segment: its header and a single record of a byte.
+/// The small quotas get split into fewer segments rather than smaller ones
+const MIN_SEGMENT_SIZE: u64 = (HEADER_SIZE + RECORD_SIZE + 1) as u64;
+
+/// Describes a single chunk of the output of a process. The index file
+/// holds these records in the order the chunks were captured in, allowing
+/// to interleave stdout and stderr the way they were produced
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Record {
+    pub descriptor: Descriptor,
+
+    /// the offset of the chunk in the output of its descriptor
+    pub offset: u64,
+
+    pub length: u32,
+
+    /// milliseconds since the UNIX epoch
+    pub time: u64,
+}
+
+impl Record {
+    fn to_bytes(self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0_u8; RECORD_SIZE];
+
+        bytes[0] = self.descriptor as u8;
+        bytes[1..9].copy_from_slice(&self.offset.to_le_bytes());
+        bytes[9..13].copy_from_slice(&self.length.to_le_bytes());
+        bytes[13..21].copy_from_slice(&self.time.to_le_bytes());
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; RECORD_SIZE]) -> Result<Record> {
+        let descriptor = match bytes[0] {
+            0 => Descriptor::Stdout,
+            1 => Descriptor::Stderr,
+            other => return Err(anyhow!("Invalid descriptor in the index file: {}", other)),
+        };
+
+        Ok(Record {
+            descriptor,
+            offset: u64::from_le_bytes(bytes[1..9].try_into()?),
+            length: u32::from_le_bytes(bytes[9..13].try_into()?),
+            time: u64::from_le_bytes(bytes[13..21].try_into()?),
+        })
+    }
+}
+
+/// Starts the index file of each segment, telling where the segment begins
+/// in the whole output
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Header {
+    /// the sequence number of the first record of the segment
+    sequence: u64,
+
+    /// the offsets in the output of each descriptor the segment starts at
+    stdout_base: u64,
+    stderr_base: u64,
+}
+
+impl Header {
+    fn to_bytes(self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0_u8; HEADER_SIZE];
+
+        bytes[0..8].copy_from_slice(&self.sequence.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.stdout_base.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.stderr_base.to_le_bytes());
+
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; HEADER_SIZE]) -> Result<Header> {
+        Ok(Header {
+            sequence: u64::from_le_bytes(bytes[0..8].try_into()?),
+            stdout_base: u64::from_le_bytes(bytes[8..16].try_into()?),
+            stderr_base: u64::from_le_bytes(bytes[16..24].try_into()?),
+        })
+    }
+
+    fn base(&self, descriptor: Descriptor) -> u64 {
+        match descriptor {
+            Descriptor::Stderr => self.stderr_base,
+            _ => self.stdout_base,
+        }
+    }
+}
+
+/// The numbers of the segments of the logs of a process kept on disk, the
+/// oldest first. Kept up to date by the writer of the logs, so that neither
+/// of its readers has to list the log directory
+#[derive(Clone, Debug, Default)]
+pub struct Segments {
+    numbers: Arc<RwLock<VecDeque<u64>>>,
+}
+
+impl Segments {
+    pub fn numbers(&self) -> Vec<u64> {
+        self.numbers.read().unwrap().iter().copied().collect()
+    }
+
+    fn push(&self, number: u64) {
+        self.numbers.write().unwrap().push_back(number);
+    }
+
+    fn remove(&self, number: u64) {
+        self.numbers.write().unwrap().retain(|kept| *kept != number);
+    }
+}
+
+/// Locates the log files of a single process. The output is kept in
+/// numbered segments, each with its own stdout, stderr and index files
+#[derive(Clone, Debug)]
+pub struct LogPaths {
+    log_dir: PathBuf,
+    id: Uuid,
+    segments: Segments,
+}
+
+impl LogPaths {
+    pub fn new(log_dir: &str, id: &Uuid, segments: &Segments) -> Self {
+        LogPaths {
+            log_dir: PathBuf::from(log_dir),
+            id: *id,
+            segments: segments.clone(),
+        }
+    }
+
+    pub fn stdout(&self, segment: u64) -> PathBuf {
+        self.path(segment, "stdout.txt")
+    }
+
+    pub fn stderr(&self, segment: u64) -> PathBuf {
+        self.path(segment, "stderr.txt")
+    }
+
+    pub fn index(&self, segment: u64) -> PathBuf {
+        self.path(segment, "index")
+    }
+
+    fn path(&self, segment: u64, suffix: &str) -> PathBuf {
+        let mut path = self.log_dir.clone();
+
+        path.push(format!("{}.{}.{}", self.id, segment, suffix));
+
+        path
+    }
+
+    /// Returns the numbers of the segments kept on disk in the ascending order
+    pub fn segments(&self) -> Vec<u64> {
+        self.segments.numbers()
+    }
+
+    /// Removes the log files of a segment. The segment stops being listed
+    /// before its files are gone. Returns the number of bytes they took on disk
+    async fn remove(&self, segment: u64) -> Result<u64> {
+        let mut size = 0;
+
+        self.segments.remove(segment);
+
+        for path in &[
+            self.index(segment),
+            self.stdout(segment),
+            self.stderr(segment),
+        ] {
+            size += tokio::fs::metadata(path)
+                .await
+                .context("Couldn't read the size of the log file")?
+                .len();
+
+            tokio::fs::remove_file(path)
+                .await
+                .context("Couldn't remove the log file")?;
+        }
+
+        Ok(size)
+    }
+
+    /// Removes all of the log files of the process. Returns the number of
+    /// bytes they took on disk
+    pub async fn remove_all(&self) -> Result<u64> {
+        let mut size = 0;
+
+        for segment in self.segments() {
+            size += self.remove(segment).await?;
+        }
+
+        Ok(size)
+    }
+}
+
+/// Lists the log files of given processes, which is only done once the
+/// server starts. Returns the number of bytes they take on disk along with
+/// the segments of each of the processes
+pub async fn scan(log_dir: &str, ids: &HashSet<Uuid>) -> Result<(u64, HashMap<Uuid, Segments>)> {
+    let mut usage = 0;
+    let mut numbers = HashMap::<Uuid, Vec<u64>>::new();
+
+    let mut entries = match tokio::fs::read_dir(log_dir).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok((0, HashMap::new())),
+        Err(err) => return Err(err).context("Couldn't list the log files"),
+    };
+
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .context("Couldn't list the log files")?
+    {
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+
+        // made of "<id>.<segment>.<suffix>"
+        let (id, rest) = match name.split_once('.') {
+            Some((id, rest)) => match Uuid::parse_str(id) {
+                Ok(id) if ids.contains(&id) => (id, rest),
+                _ => continue,
+            },
+            None => continue,
+        };
+
+        usage += entry
+            .metadata()
+            .await
+            .context("Couldn't read the size of the log file")?
+            .len();
+
+        // the index file is created last, so the other files of its
+        // segment exist already
+        if let Some(Ok(number)) = rest.strip_suffix(".index").map(str::parse) {
+            numbers.entry(id).or_default().push(number);
+        }
+    }
+
+    let segments = numbers
+        .into_iter()
+        .map(|(id, mut numbers)| {
+            numbers.sort_unstable();
+
+            let segments = Segments {
+                numbers: Arc::new(RwLock::new(numbers.into())),
+            };
+
+            (id, segments)
+        })
+        .collect();
+
+    Ok((usage, segments))
+}
+
+/// Limits of the disk space taken by the output of the processes
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Quotas {
+    /// the quota of a single process unless requested otherwise, in bytes
+    pub job: Option<u64>,
+
+    /// the quota of all of the processes together, in bytes
+    pub total: Option<u64>,
+}
+
+/// The number of bytes taken by the output of all of the processes
+pub type Usage = Arc<AtomicU64>;
+
+/// Limits applying to the output of a single process
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub quota: Option<u64>,
+    pub policy: LogPolicy,
+
+    /// the number of segments to rotate the output into with ROTATE
+    pub segments: u32,
+
+    /// the quota of all of the processes together
+    pub total: Option<u64>,
+}
+
+/// The result of appending a chunk of the output
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Appended {
+    Written,
+
+    /// the chunk didn't fit in the quotas and was dropped
+    Dropped,
+
+    /// the chunk went over the quota of a process with the KILL policy
+    Exceeded,
+}
+
+/// The segment being written to
+struct WrittenSegment {
+    number: u64,
+    stdout: File,
+    stderr: File,
+    index: File,
+
+    /// the number of bytes the segment takes on disk
+    size: u64,
+}
+
+/// Writes the output of a process into its log files, keeping it within the
+/// quotas according to its policy
+pub struct Writer {
+    paths: LogPaths,
+    limits: Limits,
+    usage: Usage,
+
+    /// the size to start the next segment at, if the output is limited
+    segment_size: Option<u64>,
+
+    /// the numbers and sizes of the previous segments still kept on disk,
+    /// the oldest first
+    previous: VecDeque<(u64, u64)>,
+
+    current: WrittenSegment,
+
+    stdout_offset: u64,
+    stderr_offset: u64,
+    sequence: u64,
+
+    /// set once the output went over the quota under the KILL policy
+    exceeded: bool,
+}
+
+impl Writer {
+    /// Creates the first segment of the log files of a process
+    pub async fn create(paths: LogPaths, limits: Limits, usage: Usage) -> Result<Writer> {
+        // the sizes include the headers and the records, as the quota does
+        let segment_size = match (limits.quota, limits.policy) {
+            (Some(quota), LogPolicy::TruncateHead) => Some(quota / RING_SEGMENTS),
+            (Some(quota), LogPolicy::Rotate) if limits.segments > 0 => {
+                Some(quota / limits.segments as u64)
+            }
+            (Some(quota), LogPolicy::Rotate) => Some(quota / DEFAULT_ROTATED_SEGMENTS),
+            _ => None,
+        }
+        .map(|size| size.max(MIN_SEGMENT_SIZE));
+
+        let header = Header {
+            sequence: 0,
+            stdout_base: 0,
+            stderr_base: 0,
+        };
+
+        let current = create_segment(&paths, 0, header, &usage).await?;
+
+        Ok(Writer {
+            paths,
+            limits,
+            usage,
+            segment_size,
+            previous: VecDeque::new(),
+            current,
+            stdout_offset: 0,
+            stderr_offset: 0,
+            sequence: 0,
+            exceeded: false,
+        })
+    }
+
+    /// Returns the number of bytes the output of the process takes on disk
+    fn size(&self) -> u64 {
+        self.current.size + self.previous.iter().map(|(_, size)| size).sum::<u64>()
+    }
+
+    /// Appends the chunk to its log file and records it in the index file.
+    /// The data is flushed before the record is written so that readers of
+    /// the index always find the data of the records they read
+    async fn append(&mut self, descriptor: Descriptor, mut data: &[u8]) -> Result<Appended> {
+        if self.exceeded {
+            return Ok(Appended::Dropped);
+        }
+
+        let mut size = (data.len() + RECORD_SIZE) as u64;
+
+        if let (Some(quota), LogPolicy::Kill) = (self.limits.quota, self.limits.policy) {
+            if self.size() + size > quota {
+                self.exceeded = true;
+                return Ok(Appended::Exceeded);
+            }
+        }
+
+        if let Some(segment_size) = self.segment_size {
+            if self.current.size > HEADER_SIZE as u64 && self.current.size + size > segment_size {
+                self.rotate().await?;
+            }
+        }
+
+        if let Some(quota) = self.limits.quota {
+            while self.size() + size > quota && !self.previous.is_empty() {
+                self.remove_oldest().await?;
+            }
+
+            // a chunk going over the whole quota on its own keeps only its
+            // newest bytes that fit
+            if self.size() + size > quota {
+                let room = quota.saturating_sub(self.size() + RECORD_SIZE as u64) as usize;
+
+                if room == 0 {
+                    return Ok(Appended::Dropped);
+                }
+
+                data = &data[data.len() - room..];
+                size = (room + RECORD_SIZE) as u64;
+            }
+        }
+
+        // only the segments of this process are removed to make room
+        // for its output
+        if let Some(total) = self.limits.total {
+            while self.usage.load(Ordering::SeqCst) + size > total {
+                if let LogPolicy::Kill = self.limits.policy {
+                    self.exceeded = true;
+                    return Ok(Appended::Exceeded);
+                } else if !self.previous.is_empty() {
+                    self.remove_oldest().await?;
+                } else {
+                    return Ok(Appended::Dropped);
+                }
+            }
+        }
+
+        let (file, offset) = match descriptor {
+            Descriptor::Stderr => (&mut self.current.stderr, &mut self.stderr_offset),
+            _ => (&mut self.current.stdout, &mut self.stdout_offset),
+        };
+
+        file.write_all(data)
+            .await
+            .context("Couldn't write to the log file")?;
+        file.flush()
+            .await
+            .context("Couldn't write to the log file")?;
+
+        let record = Record {
+            descriptor,
+            offset: *offset,
+            length: data.len() as u32,
+            time: to_millis(SystemTime::now()),
+        };
+
+        *offset += data.len() as u64;
+
+        self.current
+            .index
+            .write_all(&record.to_bytes())
+            .await
+            .context("Couldn't write to the index file")?;
+        self.current
+            .index
+            .flush()
+            .await
+            .context("Couldn't write to the index file")?;
+
+        self.sequence += 1;
+        self.current.size += size;
+        self.usage.fetch_add(size, Ordering::SeqCst);
+
+        Ok(Appended::Written)
+    }
+
+    /// Starts writing into the next segment
+    async fn rotate(&mut self) -> Result<()> {
+        let header = Header {
+            sequence: self.sequence,
+            stdout_base: self.stdout_offset,
+            stderr_base: self.stderr_offset,
+        };
+
+        let next =
+            create_segment(&self.paths, self.current.number + 1, header, &self.usage).await?;
+        let previous = std::mem::replace(&mut self.current, next);
+
+        self.previous.push_back((previous.number, previous.size));
+
+        Ok(())
+    }
+
+    async fn remove_oldest(&mut self) -> Result<()> {
+        if let Some((number, size)) = self.previous.pop_front() {
+            self.paths.remove(number).await?;
+            self.usage.fetch_sub(size, Ordering::SeqCst);
+        }
+
+        Ok(())
+    }
+}
+
+/// Creates the log files of a segment. The segment only gets listed once all
+/// of its files exist, so that the readers find them
+async fn create_segment(
+    paths: &LogPaths,
+    number: u64,
+    header: Header,
+    usage: &Usage,
+) -> Result<WrittenSegment> {
+    let stdout = File::create(paths.stdout(number))
+        .await
+        .context("Couldn't open log file for STDOUT")?;
+    let stderr = File::create(paths.stderr(number))
+        .await
+        .context("Couldn't open log file for STDERR")?;
+    let mut index = File::create(paths.index(number))
+        .await
+        .context("Couldn't open the index file")?;
+
+    index
+        .write_all(&header.to_bytes())
+        .await
+        .context("Couldn't write to the index file")?;
+    index
+        .flush()
+        .await
+        .context("Couldn't write to the index file")?;
+
+    usage.fetch_add(HEADER_SIZE as u64, Ordering::SeqCst);
+    paths.segments.push(number);
+
+    Ok(WrittenSegment {
+        number,
+        stdout,
+        stderr,
+        index,
+        size: HEADER_SIZE as u64,
+    })
+}
+
+/// Copies the output of a process from its pipes (or its pseudo-terminal)
+/// into the log files until both pipes get closed. The number of chunks
+/// captured so far is sent after each one of them. The given kill gets called
+/// once the output goes over the quota under the KILL policy
+pub async fn capture(
+    mut stdout: impl AsyncRead + Unpin,
+    mut stderr: impl AsyncRead + Unpin,
+    mut writer: Writer,
+    buffer_size: usize,
+    captured: &watch::Sender<u64>,
+    id: Uuid,
+    kill: impl FnOnce(),
+) -> Result<()> {
+    let mut stdout_buffer = vec![0_u8; buffer_size];
+    let mut stderr_buffer = vec![0_u8; buffer_size];
+    let mut stdout_open = true;
+    let mut stderr_open = true;
+    let mut dropping = false;
+    let mut kill = Some(kill);
+
+    while stdout_open || stderr_open {
+        let appended = tokio::select! {
+            read = stdout.read(&mut stdout_buffer), if stdout_open => {
+                match closed_on_eio(read).context("Couldn't read from the STDOUT pipe")? {
+                    0 => {
+                        stdout_open = false;
+                        continue;
+                    }
+                    bytes => writer.append(Descriptor::Stdout, &stdout_buffer[0..bytes]).await?,
+                }
+            }
+            read = stderr.read(&mut stderr_buffer), if stderr_open => {
+                match closed_on_eio(read).context("Couldn't read from the STDERR pipe")? {
+                    0 => {
+                        stderr_open = false;
+                        continue;
+                    }
+                    bytes => writer.append(Descriptor::Stderr, &stderr_buffer[0..bytes]).await?,
+                }
+            }
+        };
+
+        match appended {
+            Appended::Written => {
+                dropping = false;
+
+                // it's fine for no one to be following:
+                captured.send(writer.sequence).ok();
+            }
+            Appended::Dropped => {
+                if !dropping {
+                    warn!("Dropping the output of {} as it's over the quota", &id);
+                    dropping = true;
+                }
+            }
+            Appended::Exceeded => {
+                warn!("Killing {} as its output went over the quota", &id);
+
+                if let Some(kill) = kill.take() {
+                    kill();
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Treats EIO as the end of the output, as the master side of a pseudo-terminal
+/// reads it once the slave side gets closed by all of the processes
+fn closed_on_eio(read: std::io::Result<usize>) -> std::io::Result<usize> {
+    match read {
+        Err(err) if err.raw_os_error() == Some(nix::libc::EIO) => Ok(0),
+        read => read,
+    }
+}
+
+/// Reads from the file until the buffer holds a given number of bytes. Returns
+/// false if the end of the file comes first, keeping what was read in the
+/// buffer so that it can be completed once the rest is written
+async fn read_available(file: &mut File, buffer: &mut Vec<u8>, size: usize) -> Result<bool> {
+    while buffer.len() < size {
+        let mut bytes = vec![0_u8; size - buffer.len()];
+
+        let read = file
+            .read(&mut bytes)
+            .await
+            .context("Couldn't read from the index file")?;
+
+        if read == 0 {
+            return Ok(false);
+        }
+
+        buffer.extend_from_slice(&bytes[0..read]);
+    }
+
+    Ok(true)
+}
+
+/// A single segment of the log files being read
+pub struct Segment {
+    pub number: u64,
+    index: File,
+    stdout: File,
+    stderr: File,
+    header: Option<Header>,
+
+    /// the part of the header or of the record read so far
+    pending: Vec<u8>,
+
+    /// the number of records read so far
+    records: u64,
+}
+
+impl Segment {
+    pub async fn open(paths: &LogPaths, number: u64) -> Result<Segment> {
+        Ok(Segment {
+            number,
+            index: File::open(paths.index(number))
+                .await
+                .context("Couldn't open the index file")?,
+            stdout: File::open(paths.stdout(number))
+                .await
+                .context("Couldn't open log file")?,
+            stderr: File::open(paths.stderr(number))
+                .await
+                .context("Couldn't open log file")?,
+            header: None,
+            pending: Vec::with_capacity(RECORD_SIZE),
+            records: 0,
+        })
+    }
+
+    /// Returns the offset in the output of a given descriptor the segment
+    /// starts at or None if the header wasn't fully written yet
+    pub async fn base(&mut self, descriptor: Descriptor) -> Result<Option<u64>> {
+        if self.header.is_none()
+            && read_available(&mut self.index, &mut self.pending, HEADER_SIZE).await?
+        {
+            let bytes: [u8; HEADER_SIZE] = self.pending[..].try_into()?;
+            self.pending.clear();
+            self.header = Some(Header::from_bytes(&bytes)?);
+        }
+
+        Ok(self.header.map(|header| header.base(descriptor)))
+    }
+
+    /// Returns the next record along with its sequence number or None when
+    /// none was fully written yet
+    pub async fn next(&mut self) -> Result<Option<(u64, Record)>> {
+        if self.base(Descriptor::Stdout).await?.is_none() {
+            return Ok(None);
+        }
+
+        if !read_available(&mut self.index, &mut self.pending, RECORD_SIZE).await? {
+            return Ok(None);
+        }
+
+        let bytes: [u8; RECORD_SIZE] = self.pending[..].try_into()?;
+        self.pending.clear();
+
+        let sequence = self.header.map_or(0, |header| header.sequence) + self.records;
+        self.records += 1;
+
+        Ok(Some((sequence, Record::from_bytes(&bytes)?)))
+    }
+
+    /// Reads the data of a given record of this segment
+    pub async fn read(&mut self, record: &Record) -> Result<Vec<u8>> {
+        let base = self
+            .base(record.descriptor)
+            .await?
+            .ok_or_else(|| anyhow!("The header of the segment is missing"))?;
+
+        let file = match record.descriptor {
+            Descriptor::Stderr => &mut self.stderr,
+            _ => &mut self.stdout,
+        };
+
+        file.seek(SeekFrom::Start(record.offset - base))
+            .await
+            .context("Couldn't seek in the log file")?;
+
+        let mut data = vec![0_u8; record.length as usize];
+        file.read_exact(&mut data)
+            .await
+            .context("Couldn't read from the log file")?;
+
+        Ok(data)
+    }
+}
+
+/// Reads the records of the output of a process across its segments as
+/// they get appended
+pub struct Reader {
+    paths: LogPaths,
+    segment: Segment,
+}
+
+impl Reader {
+    /// Starts reading at the oldest segment kept on disk
+    pub async fn open(paths: LogPaths) -> Result<Reader> {
+        match open_from(&paths, 0).await? {
+            Some(segment) => Ok(Reader { paths, segment }),
+            None => Err(anyhow!("No log files found")),
+        }
+    }
+
+    /// Returns the next record along with its sequence number or None when
+    /// none was fully written yet
+    pub async fn next(&mut self) -> Result<Option<(u64, Record)>> {
+        loop {
+            if let Some(record) = self.segment.next().await? {
+                return Ok(Some(record));
+            }
+
+            if !self.has_next_segment() {
+                return Ok(None);
+            }
+
+            // the records appended to the current segment right before
+            // the next one got created are read first
+            if let Some(record) = self.segment.next().await? {
+                return Ok(Some(record));
+            }
+
+            match open_from(&self.paths, self.segment.number + 1).await? {
+                Some(segment) => self.segment = segment,
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Reads the data of a given record of the current segment
+    pub async fn read(&mut self, record: &Record) -> Result<Vec<u8>> {
+        self.segment.read(record).await
+    }
+
+    /// Checks if a segment following the current one was created already
+    fn has_next_segment(&self) -> bool {
+        let number = self.segment.number;
+
+        // the segments removed while being read are no longer listed, so
+        // any of the following ones could be still kept
+        self.paths
+            .segments()
+            .into_iter()
+            .any(|segment| segment > number)
+    }
+}
+
+/// Opens the oldest segment kept on disk starting with a given number. The
+/// segments removed while being opened are skipped
+async fn open_from(paths: &LogPaths, from: u64) -> Result<Option<Segment>> {
+    loop {
+        let next = paths
+            .segments()
+            .into_iter()
+            .find(|segment| *segment >= from);
+
+        match next {
+            Some(number) => match Segment::open(paths, number).await {
+                Ok(segment) => return Ok(Some(segment)),
+                Err(_) if !paths.segments().contains(&number) => continue,
+                Err(err) => return Err(err),
+            },
+            None => return Ok(None),
+        }
+    }
+}
@@ -1,14 +1,69 @@
+use crate::runner::cgroups::Accounting;
+use crate::runner::input::Input;
+use crate::runner::output::Segments;
+use crate::runner::service::RunRequest;
 use std::collections::HashMap;
 use std::process::ExitStatus;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::SystemTime;
+use tokio::sync::{mpsc, watch, RwLock};
 use uuid::Uuid;
 
 #[derive(Clone, Debug)]
 pub enum ProcessStatus {
     Running,
+
+    /// The process is running but its control groups are frozen
+    Paused,
+
     Stopped(ExitStatus),
+
+    /// The process exited while it wasn't a child of this instance of the
+    /// server, so its exit status is unknown
+    Lost,
+}
+
+/// The reasons for the server to stop a process on its own
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Termination {
+    /// the process ran for longer than its timeout
+    TimedOut,
+
+    /// the output of the process went over its quota under the KILL policy
+    LogQuotaExceeded,
+}
+
+/// A single job known to the Runner
+#[derive(Clone, Debug)]
+pub struct Process {
+    pub pid: u32,
+
+    /// start time of the process in clock ticks since the boot, if it's known
+    /// to refer to the current boot
+    pub start_time: Option<u64>,
+
+    pub status: ProcessStatus,
+    pub request: RunRequest,
+    pub started_at: SystemTime,
+    pub finished_at: Option<SystemTime>,
+
+    /// what the control groups of the process told about it once it exited
+    pub accounting: Accounting,
+
+    /// why the server stopped the process on its own, if it did
+    pub termination: Option<Termination>,
+
+    /// the number of chunks of the output captured into the log files so
+    /// far, while the capture is still going on
+    pub output: Option<watch::Receiver<u64>>,
+
+    /// the segments of the log files of the process kept on disk
+    pub segments: Segments,
+
+    /// the input written into the stdin of the process (or its pseudo-terminal)
+    /// while it's kept open
+    pub stdin: Option<mpsc::Sender<Input>>,
 }
 
 /// Atomically reference counted RwLock for a hashmap of processes states
-pub type ProcessMap = Arc<RwLock<HashMap<Uuid, (u32, ProcessStatus)>>>;
+pub type ProcessMap = Arc<RwLock<HashMap<Uuid, Process>>>;
@@ -0,0 +1,53 @@
+use std::fs::read_to_string;
+use uuid::Uuid;
+
+/// Returns the start time of a process in clock ticks since the boot as found
+/// in /proc/<pid>/stat. Together with the PID it identifies the process as the
+/// PIDs get reused
+pub fn start_time(pid: u32) -> Option<u64> {
+    stat_fields(pid).and_then(|fields| fields.get(19).and_then(|field| field.parse().ok()))
+}
+
+/// Returns the identifier of the current boot of the system
+pub fn boot_id() -> Option<String> {
+    read_to_string("/proc/sys/kernel/random/boot_id")
+        .ok()
+        .map(|id| id.trim().to_string())
+}
+
+/// Checks if the process with a given PID and start time is still running
+pub fn is_alive(pid: u32, start_time: u64) -> bool {
+    match stat_fields(pid) {
+        Some(fields) => {
+            fields.first().map(String::as_str) != Some("Z")
+                && fields.get(19).and_then(|field| field.parse().ok()) == Some(start_time)
+        }
+        None => false,
+    }
+}
+
+/// Checks if the process with a given PID is a member of the control groups
+/// created for a job with a given id
+pub fn is_in_cgroup(pid: u32, id: &Uuid) -> bool {
+    let name = format!("/{}", id);
+
+    read_to_string(format!("/proc/{}/cgroup", pid))
+        .map(|cgroups| {
+            cgroups
+                .lines()
+                .any(|line| line.rsplit(':').next() == Some(name.as_str()))
+        })
+        .unwrap_or(false)
+}
+
+/// Returns the fields of /proc/<pid>/stat that follow the command name,
+/// starting with the process state
+fn stat_fields(pid: u32) -> Option<Vec<String>> {
+    let stat = read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+
+    // the command name is put in parentheses and can contain both spaces
+    // and parentheses itself:
+    let rest = &stat[stat.rfind(')')? + 1..];
+
+    Some(rest.split_whitespace().map(String::from).collect())
+}
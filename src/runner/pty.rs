@@ -0,0 +1,167 @@
+use crate::runner::service::TerminalSize;
+use anyhow::{Context, Result};
+use nix::fcntl::{fcntl, FcntlArg, FdFlag, OFlag};
+use nix::libc;
+use nix::pty::{openpty, Winsize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::pin::Pin;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::process::Command;
+
+/// The default EOF character of the terminals, ^D
+pub const EOF: u8 = 0x04;
+
+/// A pseudo-terminal a process runs in
+pub struct Pty {
+    master: File,
+    slave: File,
+}
+
+impl Pty {
+    /// Opens a new pseudo-terminal of a given size, the default one of the
+    /// system when not given
+    pub fn open(size: Option<&TerminalSize>) -> Result<Pty> {
+        let pty =
+            openpty(size.map(winsize).as_ref(), None).context("Couldn't open a pseudo-terminal")?;
+
+        let pty = unsafe {
+            Pty {
+                master: File::from_raw_fd(pty.master),
+                slave: File::from_raw_fd(pty.slave),
+            }
+        };
+
+        // the descriptors of the server aren't meant for the processes
+        for file in &[&pty.master, &pty.slave] {
+            fcntl(file.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))
+                .context("Couldn't configure the pseudo-terminal")?;
+        }
+
+        Ok(pty)
+    }
+
+    /// Makes the slave side the stdin, the stdout and the stderr of the command
+    /// and its controlling terminal. Has to be applied after the command starts
+    /// a session of its own
+    pub fn apply(&self, cmd: &mut Command) -> Result<()> {
+        cmd.stdin(Stdio::from(self.slave.try_clone()?));
+        cmd.stdout(Stdio::from(self.slave.try_clone()?));
+        cmd.stderr(Stdio::from(self.slave.try_clone()?));
+
+        unsafe {
+            cmd.pre_exec(|| {
+                if libc::ioctl(0, libc::TIOCSCTTY, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+
+                Ok(())
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Closes the slave side, so that only the process keeps it open, and
+    /// returns the master side to read the output of the process from and
+    /// to write its input into
+    pub fn into_master(self) -> Result<(Master, Master)> {
+        // polled by the runtime rather than read in the blocking threads
+        fcntl(
+            self.master.as_raw_fd(),
+            FcntlArg::F_SETFL(OFlag::O_NONBLOCK),
+        )
+        .context("Couldn't configure the pseudo-terminal")?;
+
+        let master = Master {
+            fd: Arc::new(AsyncFd::new(self.master)?),
+        };
+
+        Ok((master.clone(), master))
+    }
+}
+
+/// The non-blocking master side of a pseudo-terminal, shared by the reader
+/// of the output and the writer of the input
+#[derive(Clone)]
+pub struct Master {
+    fd: Arc<AsyncFd<File>>,
+}
+
+impl AsRawFd for Master {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsyncRead for Master {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        loop {
+            let mut guard = match self.fd.poll_read_ready(cx) {
+                Poll::Ready(guard) => guard?,
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let unfilled = buf.initialize_unfilled();
+
+            if let Ok(read) = guard.try_io(|fd| fd.get_ref().read(unfilled)) {
+                return Poll::Ready(read.map(|bytes| buf.advance(bytes)));
+            }
+        }
+    }
+}
+
+impl AsyncWrite for Master {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        loop {
+            let mut guard = match self.fd.poll_write_ready(cx) {
+                Poll::Ready(guard) => guard?,
+                Poll::Pending => return Poll::Pending,
+            };
+
+            if let Ok(written) = guard.try_io(|fd| fd.get_ref().write(buf)) {
+                return Poll::Ready(written);
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Changes the size of the pseudo-terminal with a given master side,
+/// sending SIGWINCH to its processes
+pub fn resize(master: RawFd, size: &TerminalSize) -> Result<()> {
+    if unsafe { libc::ioctl(master, libc::TIOCSWINSZ, &winsize(size)) } == -1 {
+        return Err(std::io::Error::last_os_error()).context("Couldn't resize the pseudo-terminal");
+    }
+
+    Ok(())
+}
+
+fn winsize(size: &TerminalSize) -> Winsize {
+    Winsize {
+        ws_row: size.rows as u16,
+        ws_col: size.columns as u16,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    }
+}
@@ -0,0 +1,55 @@
+use std::time::{Duration, SystemTime};
+
+/// How often the finished processes are checked against the retention
+/// policies unless configured otherwise
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Policies of keeping the finished processes along with their logs. The
+/// processes going over any of them get deleted, the oldest first
+#[derive(Clone, Copy, Debug)]
+pub struct Retention {
+    /// how long to keep a process after it finished
+    pub max_age: Option<Duration>,
+
+    /// the number of the finished processes to keep
+    pub max_jobs: Option<usize>,
+
+    /// the number of bytes the logs of all of the processes can take on disk
+    /// before the finished ones get deleted
+    pub max_logs_size: Option<u64>,
+
+    /// how often to check the finished processes
+    pub interval: Duration,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Retention {
+            max_age: None,
+            max_jobs: None,
+            max_logs_size: None,
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+}
+
+impl Retention {
+    /// Tells if any of the policies is set
+    pub fn is_enabled(&self) -> bool {
+        self.max_age.is_some() || self.max_jobs.is_some() || self.max_logs_size.is_some()
+    }
+
+    /// Tells if a finished process should be deleted given when it finished,
+    /// the number of the finished processes kept including it and the ones
+    /// that finished after it, and the disk space taken by all of the logs
+    pub fn evicts(&self, finished_at: SystemTime, kept: usize, log_usage: u64) -> bool {
+        let too_old = match (self.max_age, finished_at.elapsed()) {
+            (Some(max_age), Ok(age)) => age > max_age,
+            _ => false,
+        };
+
+        too_old
+            || matches!(self.max_jobs, Some(max_jobs) if kept > max_jobs)
+            || matches!(self.max_logs_size, Some(max_logs_size) if log_usage > max_logs_size)
+    }
+}
@@ -1,32 +1,62 @@
+// tonic::Status is the error of every handler and of the streams they return
+#![allow(clippy::result_large_err)]
+
 use crate::runner::service::{
-    log_response, run_response, runner_server, status_response, LogRequest, LogResponse,
-    RunRequest, RunResponse, StatusRequest, StatusResponse, StopRequest, StopResponse,
+    attach_response, list_response, log_response, run_follow_response,
+    run_response::{self, RunError},
+    runner_server, stats_response, status_response, wait_response, watch_response, AttachRequest,
+    AttachResponse, DeleteRequest, DeleteResponse, ListRequest, ListResponse, LogRequest,
+    LogResponse, PauseRequest, PauseResponse, ResumeRequest, ResumeResponse, RunFollowResponse,
+    RunRequest, RunResponse, SignalRequest, SignalResponse, StatsRequest, StatsResponse,
+    StatusRequest, StatusResponse, StopRequest, StopResponse, WaitRequest, WaitResponse,
+    WatchRequest, WatchResponse,
 };
-use crate::runner::Runner;
+use crate::runner::{Runner, UsersPolicy};
 use anyhow::Result;
 use futures::stream::Stream;
 use futures::StreamExt;
-use prost::Message;
 use std::pin::Pin;
-use tonic::{Request, Response, Status};
+use tokio::sync::mpsc;
+use tonic::{Request, Response, Status, Streaming};
+use uuid::Uuid;
 use x509_parser::parse_x509_certificate;
 
+/// The common name of the client allowed whether it's in the users policy
+/// or not
+const DEFAULT_CLIENT: &str = "client";
+
+/// The number of responses waiting to be sent to an attached client
+const ATTACH_CAPACITY: usize = 16;
+
 type LogResponseStream = Pin<Box<dyn Stream<Item = Result<LogResponse, Status>> + Send + Sync>>;
+type RunFollowResponseStream =
+    Pin<Box<dyn Stream<Item = Result<RunFollowResponse, Status>> + Send + Sync>>;
+type WatchResponseStream = Pin<Box<dyn Stream<Item = Result<WatchResponse, Status>> + Send + Sync>>;
+type StatsResponseStream = Pin<Box<dyn Stream<Item = Result<StatsResponse, Status>> + Send + Sync>>;
+type AttachResponseStream =
+    Pin<Box<dyn Stream<Item = Result<AttachResponse, Status>> + Send + Sync>>;
 
 #[derive(Default)]
 pub struct RunnerServer {
     runner: Runner,
+
+    /// which users the clients can run the processes as
+    users: UsersPolicy,
 }
 
 impl RunnerServer {
-    fn authorize<T>(&self, request: &Request<T>) -> Result<(), Status>
-    where
-        T: Message,
-    {
+    // Allow dead code as the client binary doesn't use it
+    #[allow(dead_code)]
+    pub fn new(runner: Runner, users: UsersPolicy) -> Self {
+        RunnerServer { runner, users }
+    }
+
+    /// Returns the common name of the client once it's authorized
+    fn authorize<T>(&self, request: &Request<T>) -> Result<String, Status> {
         request
             .peer_certs()
             .map_or(Err(Status::permission_denied("Unauthorized!")), |certs| {
-                if certs.len() > 0 {
+                if !certs.is_empty() {
                     // let's authorize based on an immediate certificate in the chain:
                     let cert = &certs[0];
 
@@ -34,12 +64,12 @@ impl RunnerServer {
                         if let Some(attr) = certificate.subject().iter_common_name().next() {
                             match attr.as_str() {
                                 Ok(common_name) => {
-                                    // A more real solution would keep the list of common names configured
-                                    // somewhere and loaded into &self here and compare. For simplicity given
-                                    // it's a proof-of-concept let's just hardcode the expected value:
-
-                                    if common_name == "client" {
-                                        Ok(())
+                                    // the clients listed in the users policy are allowed along with
+                                    // the default one
+                                    if common_name == DEFAULT_CLIENT
+                                        || self.users.knows(common_name)
+                                    {
+                                        Ok(common_name.to_string())
                                     } else {
                                         Err(Status::permission_denied("Unauthorized!"))
                                     }
@@ -65,18 +95,29 @@ impl RunnerServer {
                 }
             })
     }
+
+    /// Starts the process once the client is allowed to run it as the
+    /// requested user and groups
+    async fn start(&self, client: &str, request: &RunRequest) -> Result<Uuid, RunError> {
+        let request = self.users.apply(client, request)?;
+        self.runner.run(&request).await
+    }
 }
 
 #[tonic::async_trait]
 impl runner_server::Runner for RunnerServer {
     type LogStream = LogResponseStream;
+    type WatchStream = WatchResponseStream;
+    type RunFollowStream = RunFollowResponseStream;
+    type StatsFollowStream = StatsResponseStream;
+    type AttachStream = AttachResponseStream;
 
     async fn run(&self, request: Request<RunRequest>) -> Result<Response<RunResponse>, Status> {
-        self.authorize(&request)?;
+        let client = self.authorize(&request)?;
 
         let run_request = request.into_inner();
 
-        match self.runner.run(&run_request).await {
+        match self.start(&client, &run_request).await {
             Ok(id) => Ok(Response::new(RunResponse {
                 results: Some(run_response::Results::Id(id.to_string())),
             })),
@@ -97,6 +138,62 @@ impl runner_server::Runner for RunnerServer {
         }
     }
 
+    async fn signal(
+        &self,
+        request: Request<SignalRequest>,
+    ) -> Result<Response<SignalResponse>, Status> {
+        self.authorize(&request)?;
+
+        let signal_request = request.into_inner();
+
+        match self.runner.signal(&signal_request).await {
+            Ok(_) => Ok(Response::new(SignalResponse { error: None })),
+            Err(err) => Ok(Response::new(SignalResponse { error: Some(err) })),
+        }
+    }
+
+    async fn pause(
+        &self,
+        request: Request<PauseRequest>,
+    ) -> Result<Response<PauseResponse>, Status> {
+        self.authorize(&request)?;
+
+        let pause_request = request.into_inner();
+
+        match self.runner.pause(&pause_request).await {
+            Ok(_) => Ok(Response::new(PauseResponse { error: None })),
+            Err(err) => Ok(Response::new(PauseResponse { error: Some(err) })),
+        }
+    }
+
+    async fn resume(
+        &self,
+        request: Request<ResumeRequest>,
+    ) -> Result<Response<ResumeResponse>, Status> {
+        self.authorize(&request)?;
+
+        let resume_request = request.into_inner();
+
+        match self.runner.resume(&resume_request).await {
+            Ok(_) => Ok(Response::new(ResumeResponse { error: None })),
+            Err(err) => Ok(Response::new(ResumeResponse { error: Some(err) })),
+        }
+    }
+
+    async fn delete(
+        &self,
+        request: Request<DeleteRequest>,
+    ) -> Result<Response<DeleteResponse>, Status> {
+        self.authorize(&request)?;
+
+        let delete_request = request.into_inner();
+
+        match self.runner.delete(&delete_request).await {
+            Ok(_) => Ok(Response::new(DeleteResponse { error: None })),
+            Err(err) => Ok(Response::new(DeleteResponse { error: Some(err) })),
+        }
+    }
+
     async fn status(
         &self,
         request: Request<StatusRequest>,
@@ -126,8 +223,8 @@ impl runner_server::Runner for RunnerServer {
         match self.runner.log(&log_request).await {
             Ok(result) => {
                 let ret = result.map(|item| match item {
-                    Ok(data) => Ok(LogResponse {
-                        results: Some(log_response::Results::Data(data)),
+                    Ok(chunk) => Ok(LogResponse {
+                        results: Some(log_response::Results::Chunk(chunk)),
                     }),
                     Err(err) => Ok(LogResponse {
                         results: Some(log_response::Results::Error(err)),
@@ -151,4 +248,237 @@ impl runner_server::Runner for RunnerServer {
             }
         }
     }
+
+    async fn list(&self, request: Request<ListRequest>) -> Result<Response<ListResponse>, Status> {
+        self.authorize(&request)?;
+
+        let list_request = request.into_inner();
+
+        match self.runner.list(&list_request).await {
+            Ok((jobs, next_page_token)) => Ok(Response::new(ListResponse {
+                results: Some(list_response::Results::Result(list_response::JobList {
+                    jobs,
+                    next_page_token,
+                })),
+            })),
+            Err(err) => Ok(Response::new(ListResponse {
+                results: Some(list_response::Results::Error(err)),
+            })),
+        }
+    }
+
+    async fn watch(
+        &self,
+        request: Request<WatchRequest>,
+    ) -> Result<Response<WatchResponseStream>, Status> {
+        self.authorize(&request)?;
+
+        let watch_request = request.into_inner();
+
+        match self.runner.watch(&watch_request).await {
+            Ok(result) => {
+                let ret = result.map(|item| match item {
+                    Ok(event) => Ok(WatchResponse {
+                        results: Some(watch_response::Results::Event(event)),
+                    }),
+                    Err(err) => Ok(WatchResponse {
+                        results: Some(watch_response::Results::Error(err)),
+                    }),
+                });
+                Ok(Response::new(Box::pin(ret)))
+            }
+            Err(err) => {
+                let ret = futures::stream::once(async move {
+                    Ok(WatchResponse {
+                        results: Some(watch_response::Results::Error(err)),
+                    })
+                });
+
+                Ok(Response::new(Box::pin(ret)))
+            }
+        }
+    }
+
+    async fn wait(&self, request: Request<WaitRequest>) -> Result<Response<WaitResponse>, Status> {
+        self.authorize(&request)?;
+
+        let wait_request = request.into_inner();
+
+        match self.runner.wait(&wait_request).await {
+            Ok(result) => Ok(Response::new(WaitResponse {
+                results: Some(wait_response::Results::Result(result)),
+            })),
+            Err(err) => Ok(Response::new(WaitResponse {
+                results: Some(wait_response::Results::Error(err)),
+            })),
+        }
+    }
+
+    async fn run_follow(
+        &self,
+        request: Request<RunRequest>,
+    ) -> Result<Response<RunFollowResponseStream>, Status> {
+        let client = self.authorize(&request)?;
+
+        let run_request = request.into_inner();
+
+        let results: Pin<Box<dyn Stream<Item = run_follow_response::Results> + Send + Sync>> =
+            match self.start(&client, &run_request).await {
+                Ok(id) => {
+                    let started = futures::stream::once(async move {
+                        run_follow_response::Results::Id(id.to_string())
+                    });
+
+                    match self.runner.follow(&id, 0).await {
+                        Ok(follow) => Box::pin(started.chain(follow)),
+                        Err(err) => Box::pin(started.chain(futures::stream::once(async move {
+                            run_follow_response::Results::LogError(err)
+                        }))),
+                    }
+                }
+                Err(err) => Box::pin(futures::stream::once(async move {
+                    run_follow_response::Results::RunError(err)
+                })),
+            };
+
+        let ret = results.map(|results| {
+            Ok(RunFollowResponse {
+                results: Some(results),
+            })
+        });
+
+        Ok(Response::new(Box::pin(ret)))
+    }
+
+    async fn stats(
+        &self,
+        request: Request<StatsRequest>,
+    ) -> Result<Response<StatsResponse>, Status> {
+        self.authorize(&request)?;
+
+        let stats_request = request.into_inner();
+
+        match self.runner.stats(&stats_request).await {
+            Ok(usage) => Ok(Response::new(StatsResponse {
+                results: Some(stats_response::Results::Result(usage)),
+            })),
+            Err(err) => Ok(Response::new(StatsResponse {
+                results: Some(stats_response::Results::Error(err)),
+            })),
+        }
+    }
+
+    async fn stats_follow(
+        &self,
+        request: Request<StatsRequest>,
+    ) -> Result<Response<StatsResponseStream>, Status> {
+        self.authorize(&request)?;
+
+        let stats_request = request.into_inner();
+
+        match self.runner.stats_follow(&stats_request).await {
+            Ok(result) => {
+                let ret = result.map(|item| match item {
+                    Ok(usage) => Ok(StatsResponse {
+                        results: Some(stats_response::Results::Result(usage)),
+                    }),
+                    Err(err) => Ok(StatsResponse {
+                        results: Some(stats_response::Results::Error(err)),
+                    }),
+                });
+                Ok(Response::new(Box::pin(ret)))
+            }
+            Err(err) => {
+                let ret = futures::stream::once(async move {
+                    Ok(StatsResponse {
+                        results: Some(stats_response::Results::Error(err)),
+                    })
+                });
+
+                Ok(Response::new(Box::pin(ret)))
+            }
+        }
+    }
+
+    async fn attach(
+        &self,
+        request: Request<Streaming<AttachRequest>>,
+    ) -> Result<Response<AttachResponseStream>, Status> {
+        self.authorize(&request)?;
+
+        let mut inbound = request.into_inner();
+
+        let attach_request = match inbound.message().await? {
+            Some(attach_request) => attach_request,
+            None => return Err(Status::invalid_argument("No process to attach to given")),
+        };
+
+        let mut output = match self.runner.attach(&attach_request).await {
+            Ok(output) => output,
+            Err(err) => {
+                let ret = futures::stream::once(async move {
+                    Ok(AttachResponse {
+                        results: Some(attach_response::Results::Error(err)),
+                    })
+                });
+
+                return Ok(Response::new(Box::pin(ret)));
+            }
+        };
+
+        // validated above:
+        let id = Uuid::parse_str(&attach_request.id).unwrap();
+        let runner = self.runner.clone();
+        let (sender, receiver) = mpsc::channel(ATTACH_CAPACITY);
+
+        // forwards the input of the client on its own, so that a process not
+        // reading it doesn't hold back its output
+        let input_sender = sender.clone();
+        let input = tokio::spawn(async move {
+            // the client might be done with the input but still read the
+            // output, which is forwarded until it ends anyway
+            while let Ok(Some(attach_request)) = inbound.message().await {
+                if let Err(err) = runner.write_stdin(&id, &attach_request).await {
+                    let response = AttachResponse {
+                        results: Some(attach_response::Results::Error(err)),
+                    };
+
+                    if input_sender.send(Ok(response)).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        // forwards the output of the process until it ends, or the client goes
+        // away - noticed as soon as it happens rather than on the next output,
+        // which might never come
+        tokio::spawn(async move {
+            loop {
+                let results = tokio::select! {
+                    results = output.next() => match results {
+                        Some(results) => results,
+                        None => break,
+                    },
+                    _ = sender.closed() => break,
+                };
+
+                let response = AttachResponse {
+                    results: Some(results),
+                };
+
+                if sender.send(Ok(response)).await.is_err() {
+                    break;
+                }
+            }
+
+            input.abort();
+        });
+
+        let ret = futures::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|item| (item, receiver))
+        });
+
+        Ok(Response::new(Box::pin(ret)))
+    }
 }
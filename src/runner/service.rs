@@ -1,3 +1,6 @@
+// the values of the proto enums share one scope, hence the Error suffixes
+#![allow(clippy::enum_variant_names)]
+
 tonic::include_proto!("service");
 
 macro_rules! impl_from_anyhow {
@@ -53,6 +56,104 @@ impl std::fmt::Display for stop_response::stop_error::Error {
             stop_response::stop_error::Error::ProcessNotFoundError => {
                 write!(f, "Process not found")
             }
+            stop_response::stop_error::Error::InvalidSignalError => {
+                write!(f, "Invalid signal")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for pause_response::pause_error::Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            pause_response::pause_error::Error::InvalidId => {
+                write!(f, "Invalid process id")
+            }
+            pause_response::pause_error::Error::ProcessNotFoundError => {
+                write!(f, "Process not found")
+            }
+            pause_response::pause_error::Error::ProcessAlreadyStoppedError => {
+                write!(f, "Process already stopped")
+            }
+            pause_response::pause_error::Error::ProcessAlreadyPausedError => {
+                write!(f, "Process already paused")
+            }
+            pause_response::pause_error::Error::NotConstrainedError => {
+                write!(
+                    f,
+                    "Process can't be paused as it has no control groups (it isn't constrained)"
+                )
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for resume_response::resume_error::Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            resume_response::resume_error::Error::InvalidId => {
+                write!(f, "Invalid process id")
+            }
+            resume_response::resume_error::Error::ProcessNotFoundError => {
+                write!(f, "Process not found")
+            }
+            resume_response::resume_error::Error::ProcessNotPausedError => {
+                write!(f, "Process isn't paused")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for signal_response::signal_error::Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            signal_response::signal_error::Error::InvalidId => {
+                write!(f, "Invalid process id")
+            }
+            signal_response::signal_error::Error::ProcessNotFoundError => {
+                write!(f, "Process not found")
+            }
+            signal_response::signal_error::Error::ProcessAlreadyStoppedError => {
+                write!(f, "Process already stopped")
+            }
+            signal_response::signal_error::Error::InvalidSignalError => {
+                write!(f, "Invalid signal")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for attach_response::attach_error::Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            attach_response::attach_error::Error::InvalidId => {
+                write!(f, "Invalid process id")
+            }
+            attach_response::attach_error::Error::ProcessNotFoundError => {
+                write!(f, "Process not found")
+            }
+            attach_response::attach_error::Error::StdinClosedError => {
+                write!(f, "The stdin of the process is closed")
+            }
+            attach_response::attach_error::Error::NotTtyError => {
+                write!(f, "The process doesn't run in a pseudo-terminal")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for delete_response::delete_error::Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            delete_response::delete_error::Error::InvalidId => {
+                write!(f, "Invalid process id")
+            }
+            delete_response::delete_error::Error::ProcessNotFoundError => {
+                write!(f, "Process not found")
+            }
+            delete_response::delete_error::Error::ProcessRunningError => {
+                write!(f, "Process is still running")
+            }
         }
     }
 }
@@ -63,6 +164,42 @@ impl std::fmt::Display for run_response::run_error::Error {
             run_response::run_error::Error::NameEmptyError => {
                 write!(f, "Command name empty")
             }
+            run_response::run_error::Error::InvalidCpuQuotaError => {
+                write!(
+                    f,
+                    "Invalid CPU quota: it has to be at least 0.01 of a CPU and at most the number of CPUs it can run on"
+                )
+            }
+            run_response::run_error::Error::InvalidCpusetError => {
+                write!(f, "Invalid cpuset: expected a list of ids like 0-3,6")
+            }
+            run_response::run_error::Error::UnknownDeviceError => {
+                write!(f, "Unknown disk device")
+            }
+            run_response::run_error::Error::InvalidDiskRuleError => {
+                write!(f, "Invalid disk rule")
+            }
+            run_response::run_error::Error::InvalidWorkingDirError => {
+                write!(f, "Working directory doesn't exist or isn't a directory")
+            }
+            run_response::run_error::Error::InvalidEnvError => {
+                write!(f, "Invalid environment variable")
+            }
+            run_response::run_error::Error::UnknownUserError => {
+                write!(f, "Unknown user")
+            }
+            run_response::run_error::Error::UnknownGroupError => {
+                write!(f, "Unknown group")
+            }
+            run_response::run_error::Error::UserNotAllowedError => {
+                write!(f, "Not allowed to run commands as the user")
+            }
+            run_response::run_error::Error::GroupNotAllowedError => {
+                write!(f, "Not allowed to run commands in the group")
+            }
+            run_response::run_error::Error::InvalidTimeoutError => {
+                write!(f, "Invalid timeout: it has to be at least 1 second")
+            }
         }
     }
 }
@@ -89,6 +226,70 @@ impl std::fmt::Display for log_response::log_error::Error {
             log_response::log_error::Error::ProcessNotFoundError => {
                 write!(f, "Process not found")
             }
+            log_response::log_error::Error::InvalidRange => {
+                write!(
+                    f,
+                    "Invalid range: the offset can't be combined with tail or with both descriptors"
+                )
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for list_response::list_error::Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            list_response::list_error::Error::InvalidPageToken => {
+                write!(f, "Invalid page token")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for watch_response::watch_error::Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            watch_response::watch_error::Error::InvalidId => {
+                write!(f, "Invalid process id")
+            }
+            watch_response::watch_error::Error::EventsMissed => {
+                write!(f, "Events missed")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for wait_response::wait_error::Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            wait_response::wait_error::Error::InvalidId => {
+                write!(f, "Invalid process id")
+            }
+            wait_response::wait_error::Error::ProcessNotFoundError => {
+                write!(f, "Process not found")
+            }
+            wait_response::wait_error::Error::TimeoutError => {
+                write!(f, "Timed out waiting for the process")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for stats_response::stats_error::Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            stats_response::stats_error::Error::InvalidId => {
+                write!(f, "Invalid process id")
+            }
+            stats_response::stats_error::Error::ProcessNotFoundError => {
+                write!(f, "Process not found")
+            }
+            stats_response::stats_error::Error::NotAccountedError => {
+                write!(
+                    f,
+                    "Resource usage of the process isn't accounted as it isn't constrained"
+                )
+            }
         }
     }
 }
@@ -102,6 +303,16 @@ impl std::convert::From<run_response::run_error::Error> for run_response::RunErr
     }
 }
 
+impl run_response::RunError {
+    /// Creates an error of a given kind with the details of what caused it
+    pub fn with_details(error: run_response::run_error::Error, details: &str) -> Self {
+        run_response::RunError {
+            description: format!("{}: {}", error, details),
+            errors: Some(run_response::run_error::Errors::RunError(error as i32)),
+        }
+    }
+}
+
 impl std::convert::From<stop_response::stop_error::Error> for stop_response::StopError {
     fn from(error: stop_response::stop_error::Error) -> stop_response::StopError {
         stop_response::StopError {
@@ -111,6 +322,61 @@ impl std::convert::From<stop_response::stop_error::Error> for stop_response::Sto
     }
 }
 
+impl std::convert::From<pause_response::pause_error::Error> for pause_response::PauseError {
+    fn from(error: pause_response::pause_error::Error) -> pause_response::PauseError {
+        pause_response::PauseError {
+            description: format!("{}", error),
+            errors: Some(pause_response::pause_error::Errors::PauseError(
+                error as i32,
+            )),
+        }
+    }
+}
+
+impl std::convert::From<resume_response::resume_error::Error> for resume_response::ResumeError {
+    fn from(error: resume_response::resume_error::Error) -> resume_response::ResumeError {
+        resume_response::ResumeError {
+            description: format!("{}", error),
+            errors: Some(resume_response::resume_error::Errors::ResumeError(
+                error as i32,
+            )),
+        }
+    }
+}
+
+impl std::convert::From<signal_response::signal_error::Error> for signal_response::SignalError {
+    fn from(error: signal_response::signal_error::Error) -> signal_response::SignalError {
+        signal_response::SignalError {
+            description: format!("{}", error),
+            errors: Some(signal_response::signal_error::Errors::SignalError(
+                error as i32,
+            )),
+        }
+    }
+}
+
+impl std::convert::From<attach_response::attach_error::Error> for attach_response::AttachError {
+    fn from(error: attach_response::attach_error::Error) -> attach_response::AttachError {
+        attach_response::AttachError {
+            description: format!("{}", error),
+            errors: Some(attach_response::attach_error::Errors::AttachError(
+                error as i32,
+            )),
+        }
+    }
+}
+
+impl std::convert::From<delete_response::delete_error::Error> for delete_response::DeleteError {
+    fn from(error: delete_response::delete_error::Error) -> delete_response::DeleteError {
+        delete_response::DeleteError {
+            description: format!("{}", error),
+            errors: Some(delete_response::delete_error::Errors::DeleteError(
+                error as i32,
+            )),
+        }
+    }
+}
+
 impl std::convert::From<status_response::status_error::Error> for status_response::StatusError {
     fn from(error: status_response::status_error::Error) -> status_response::StatusError {
         status_response::StatusError {
@@ -131,6 +397,46 @@ impl std::convert::From<log_response::log_error::Error> for log_response::LogErr
     }
 }
 
+impl std::convert::From<list_response::list_error::Error> for list_response::ListError {
+    fn from(error: list_response::list_error::Error) -> list_response::ListError {
+        list_response::ListError {
+            description: format!("{}", error),
+            errors: Some(list_response::list_error::Errors::ListError(error as i32)),
+        }
+    }
+}
+
+impl std::convert::From<watch_response::watch_error::Error> for watch_response::WatchError {
+    fn from(error: watch_response::watch_error::Error) -> watch_response::WatchError {
+        watch_response::WatchError {
+            description: format!("{}", error),
+            errors: Some(watch_response::watch_error::Errors::WatchError(
+                error as i32,
+            )),
+        }
+    }
+}
+
+impl std::convert::From<wait_response::wait_error::Error> for wait_response::WaitError {
+    fn from(error: wait_response::wait_error::Error) -> wait_response::WaitError {
+        wait_response::WaitError {
+            description: format!("{}", error),
+            errors: Some(wait_response::wait_error::Errors::WaitError(error as i32)),
+        }
+    }
+}
+
+impl std::convert::From<stats_response::stats_error::Error> for stats_response::StatsError {
+    fn from(error: stats_response::stats_error::Error) -> stats_response::StatsError {
+        stats_response::StatsError {
+            description: format!("{}", error),
+            errors: Some(stats_response::stats_error::Errors::StatsError(
+                error as i32,
+            )),
+        }
+    }
+}
+
 impl_from_anyhow!(
     run_response::RunError,
     run_response::run_error::Errors::GeneralError
@@ -141,6 +447,31 @@ impl_from_anyhow!(
     stop_response::stop_error::Errors::GeneralError
 );
 
+impl_from_anyhow!(
+    pause_response::PauseError,
+    pause_response::pause_error::Errors::GeneralError
+);
+
+impl_from_anyhow!(
+    resume_response::ResumeError,
+    resume_response::resume_error::Errors::GeneralError
+);
+
+impl_from_anyhow!(
+    signal_response::SignalError,
+    signal_response::signal_error::Errors::GeneralError
+);
+
+impl_from_anyhow!(
+    attach_response::AttachError,
+    attach_response::attach_error::Errors::GeneralError
+);
+
+impl_from_anyhow!(
+    delete_response::DeleteError,
+    delete_response::delete_error::Errors::GeneralError
+);
+
 impl_from_anyhow!(
     status_response::StatusError,
     status_response::status_error::Errors::GeneralError
@@ -151,6 +482,26 @@ impl_from_anyhow!(
     log_response::log_error::Errors::GeneralError
 );
 
+impl_from_anyhow!(
+    list_response::ListError,
+    list_response::list_error::Errors::GeneralError
+);
+
+impl_from_anyhow!(
+    watch_response::WatchError,
+    watch_response::watch_error::Errors::GeneralError
+);
+
+impl_from_anyhow!(
+    wait_response::WaitError,
+    wait_response::wait_error::Errors::GeneralError
+);
+
+impl_from_anyhow!(
+    stats_response::StatsError,
+    stats_response::stats_error::Errors::GeneralError
+);
+
 impl_from_internal_error!(
     run_response::RunError,
     run_response::run_error::Errors::RunError
@@ -161,6 +512,31 @@ impl_from_internal_error!(
     stop_response::stop_error::Errors::StopError
 );
 
+impl_from_internal_error!(
+    pause_response::PauseError,
+    pause_response::pause_error::Errors::PauseError
+);
+
+impl_from_internal_error!(
+    resume_response::ResumeError,
+    resume_response::resume_error::Errors::ResumeError
+);
+
+impl_from_internal_error!(
+    signal_response::SignalError,
+    signal_response::signal_error::Errors::SignalError
+);
+
+impl_from_internal_error!(
+    attach_response::AttachError,
+    attach_response::attach_error::Errors::AttachError
+);
+
+impl_from_internal_error!(
+    delete_response::DeleteError,
+    delete_response::delete_error::Errors::DeleteError
+);
+
 impl_from_internal_error!(
     status_response::StatusError,
     status_response::status_error::Errors::StatusError
@@ -170,3 +546,23 @@ impl_from_internal_error!(
     log_response::LogError,
     log_response::log_error::Errors::LogError
 );
+
+impl_from_internal_error!(
+    list_response::ListError,
+    list_response::list_error::Errors::ListError
+);
+
+impl_from_internal_error!(
+    watch_response::WatchError,
+    watch_response::watch_error::Errors::WatchError
+);
+
+impl_from_internal_error!(
+    wait_response::WaitError,
+    wait_response::wait_error::Errors::WaitError
+);
+
+impl_from_internal_error!(
+    stats_response::StatsError,
+    stats_response::stats_error::Errors::StatsError
+);
@@ -0,0 +1,215 @@
+use crate::runner::service::{
+    run_response::{run_error, RunError},
+    RunRequest,
+};
+use anyhow::{anyhow, Context, Result};
+use nix::unistd::{getgrouplist, setgid, setgroups, setuid, Gid, Group, Uid, User};
+use std::collections::HashMap;
+use std::ffi::CString;
+use tokio::process::Command;
+
+/// Stands for any of the users in the policy
+const ANY_USER: &str = "*";
+
+/// The user and the groups a process runs as, the ones of the server being
+/// kept when not given
+pub struct Credentials {
+    pub user: Option<User>,
+    gid: Option<Gid>,
+    groups: Option<Vec<Gid>>,
+}
+
+/// Which users the clients can run the processes as, keyed by the common
+/// names of their certificates, which also makes the clients authorized.
+/// Any of them can run the processes as the default user of the policy, or
+/// as the user of the server when there's none
+#[derive(Default)]
+pub struct UsersPolicy {
+    allowed: HashMap<String, Vec<String>>,
+
+    /// the user the processes run as when the request doesn't name one
+    default_user: Option<User>,
+}
+
+impl UsersPolicy {
+    /// Creates the policy out of the rules listing the users of each client.
+    /// The rules given for the same client add up
+    // Allow dead code as the client binary doesn't use it
+    #[allow(dead_code)]
+    pub fn new(rules: Vec<(String, Vec<String>)>, default_user: Option<&str>) -> Result<Self> {
+        let mut allowed = HashMap::<String, Vec<String>>::new();
+
+        for (client, users) in rules {
+            allowed.entry(client).or_default().extend(users);
+        }
+
+        let default_user = match default_user {
+            Some(user) => Some(resolve_user(user).map_err(|err| anyhow!(err.description))?),
+            None => None,
+        };
+
+        Ok(UsersPolicy {
+            allowed,
+            default_user,
+        })
+    }
+
+    /// Tells if the client with a given common name is listed in the policy
+    pub fn knows(&self, client: &str) -> bool {
+        self.allowed.contains_key(client)
+    }
+
+    /// Checks if the client with a given common name can run the process as
+    /// the user and in the groups of a given request. Returns the request to
+    /// run, naming the default user when the given one doesn't name any
+    pub fn apply(&self, client: &str, request: &RunRequest) -> Result<RunRequest, RunError> {
+        if request.user.is_empty() {
+            return match &self.default_user {
+                Some(user) => Ok(RunRequest {
+                    user: user.name.clone(),
+                    ..request.clone()
+                }),
+                // the user of the server can be a member of any group, so
+                // the groups are only given along with a user of the policy
+                None if !request.group.is_empty() || !request.supplementary_groups.is_empty() => {
+                    Err(RunError::with_details(
+                        run_error::Error::GroupNotAllowedError,
+                        "the groups can only be given along with the user",
+                    ))
+                }
+                None => Ok(request.clone()),
+            };
+        }
+
+        let user = resolve_user(&request.user)?;
+
+        let allowed = match self.allowed.get(client) {
+            Some(users) => users
+                .iter()
+                .any(|allowed| allowed == ANY_USER || *allowed == user.name),
+            None => false,
+        } || matches!(&self.default_user, Some(default) if default.uid == user.uid);
+
+        if allowed {
+            Ok(request.clone())
+        } else {
+            Err(RunError::with_details(
+                run_error::Error::UserNotAllowedError,
+                &user.name,
+            ))
+        }
+    }
+}
+
+/// Resolves the user and the groups of a given request. When only the user
+/// is given, the process gets its primary group and all of the groups it's
+/// a member of. The groups given along with the user have to be its own
+pub fn credentials(request: &RunRequest) -> Result<Credentials, RunError> {
+    let user = match request.user.as_str() {
+        "" => None,
+        user => Some(resolve_user(user)?),
+    };
+
+    let gid = match request.group.as_str() {
+        "" => user.as_ref().map(|user| user.gid),
+        group => Some(resolve_group(group)?),
+    };
+
+    let supplementary = request
+        .supplementary_groups
+        .iter()
+        .map(|group| resolve_group(group))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let groups = match (&user, gid) {
+        (Some(user), Some(gid)) => {
+            // along with the primary group, as the list always has the
+            // one it's given
+            let member_of = member_of(user, user.gid)?;
+
+            let requested = std::iter::once(&request.group).chain(&request.supplementary_groups);
+            let gids = std::iter::once(gid).chain(supplementary.iter().copied());
+
+            if let Some((group, _)) = requested
+                .zip(gids)
+                .find(|(group, gid)| !group.is_empty() && !member_of.contains(gid))
+            {
+                return Err(RunError::with_details(
+                    run_error::Error::GroupNotAllowedError,
+                    &format!("{} isn't a member of {}", user.name, group),
+                ));
+            }
+
+            if supplementary.is_empty() {
+                Some(member_of)
+            } else {
+                Some(supplementary)
+            }
+        }
+        _ if supplementary.is_empty() => None,
+        _ => Some(supplementary),
+    };
+
+    Ok(Credentials { user, gid, groups })
+}
+
+impl Credentials {
+    /// Makes the command drop the privileges of the server right before it
+    /// gets executed. Has to be applied after the command joins its control
+    /// groups, which takes the privileges
+    pub fn apply_pre_exec(&self, cmd: &mut Command) {
+        let uid = self.user.as_ref().map(|user| user.uid);
+        let gid = self.gid;
+        let groups = self.groups.clone();
+
+        unsafe {
+            cmd.pre_exec(move || {
+                // the groups can only be changed before giving up the root
+                if let Some(groups) = &groups {
+                    setgroups(groups).map_err(|_| std::io::Error::last_os_error())?;
+                }
+
+                if let Some(gid) = gid {
+                    setgid(gid).map_err(|_| std::io::Error::last_os_error())?;
+                }
+
+                if let Some(uid) = uid {
+                    setuid(uid).map_err(|_| std::io::Error::last_os_error())?;
+                }
+
+                Ok(())
+            });
+        }
+    }
+}
+
+/// Finds the user given either by its name or by its UID
+fn resolve_user(user: &str) -> Result<User, RunError> {
+    let found = match user.parse() {
+        Ok(uid) => User::from_uid(Uid::from_raw(uid)),
+        Err(_) => User::from_name(user),
+    }
+    .context("Couldn't look up the user")?;
+
+    found.ok_or_else(|| RunError::with_details(run_error::Error::UnknownUserError, user))
+}
+
+/// Finds the GID of the group given either by its name or by its GID
+fn resolve_group(group: &str) -> Result<Gid, RunError> {
+    let found = match group.parse() {
+        Ok(gid) => Group::from_gid(Gid::from_raw(gid)),
+        Err(_) => Group::from_name(group),
+    }
+    .context("Couldn't look up the group")?;
+
+    found
+        .map(|group| group.gid)
+        .ok_or_else(|| RunError::with_details(run_error::Error::UnknownGroupError, group))
+}
+
+/// Lists the groups a given user is a member of, including a given one
+fn member_of(user: &User, gid: Gid) -> Result<Vec<Gid>, RunError> {
+    let name = CString::new(user.name.as_str()).context("Invalid user name")?;
+
+    Ok(getgrouplist(&name, gid).context("Couldn't list the groups of the user")?)
+}
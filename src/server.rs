@@ -1,5 +1,7 @@
 mod cipher;
-mod cli;
+mod cli {
+    pub mod server;
+}
 mod runner;
 mod tls;
 
@@ -7,6 +9,9 @@ use crate::runner::service::runner_server;
 use anyhow::{Context, Result};
 use cli::server::Cli;
 use runner::server::RunnerServer;
+use runner::{Quotas, Retention, Runner, UsersPolicy};
+use std::path::Path;
+use std::time::Duration;
 use structopt::StructOpt;
 use tls::server_config;
 use tonic::transport::{Server, ServerTlsConfig};
@@ -32,7 +37,24 @@ async fn start_server(args: Cli) -> Result<()> {
         .parse()
         .context("Failed to parse the server bind address")?;
 
-    let server = RunnerServer::default();
+    let quotas = Quotas {
+        job: args.max_log_size,
+        total: args.max_logs_size,
+    };
+
+    let retention = Retention {
+        max_age: args.retain_for.map(Duration::from_secs),
+        max_jobs: args.retain_jobs,
+        max_logs_size: args.retain_logs_size,
+        interval: Duration::from_secs(args.retention_interval),
+    };
+
+    let runner = Runner::open(args.log_dir, Path::new(&args.journal), quotas, retention)
+        .await
+        .context("Failed to load the jobs journal")?;
+    let users = UsersPolicy::new(args.allow_users, args.default_user.as_deref())
+        .context("Failed to load the users policy")?;
+    let server = RunnerServer::new(runner, users);
     let tls_config = server_config(args.cert, args.key, args.client_ca, args.cipher).await?;
     let mut tls = ServerTlsConfig::new();
 
@@ -0,0 +1,57 @@
+use crate::runner::service::TerminalSize;
+use anyhow::{Context, Result};
+use nix::libc;
+use nix::pty::Winsize;
+use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg, Termios};
+use nix::unistd::isatty;
+
+const STDIN: i32 = 0;
+
+/// Keeps the local terminal in the raw mode, passing every key straight to
+/// the command, until dropped
+pub struct RawMode {
+    original: Termios,
+}
+
+impl RawMode {
+    pub fn enable() -> Result<RawMode> {
+        let original = tcgetattr(STDIN).context("Couldn't read the terminal settings")?;
+
+        let mut raw = original.clone();
+        cfmakeraw(&mut raw);
+
+        tcsetattr(STDIN, SetArg::TCSANOW, &raw).context("Couldn't put the terminal in raw mode")?;
+
+        Ok(RawMode { original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        tcsetattr(STDIN, SetArg::TCSANOW, &self.original).ok();
+    }
+}
+
+/// Tells whether the stdin of the client is a terminal
+pub fn is_terminal() -> bool {
+    matches!(isatty(STDIN), Ok(true))
+}
+
+/// Returns the size of the local terminal, None when the stdin isn't one
+pub fn size() -> Option<TerminalSize> {
+    let mut winsize = Winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    if unsafe { libc::ioctl(STDIN, libc::TIOCGWINSZ, &mut winsize) } == -1 {
+        return None;
+    }
+
+    Some(TerminalSize {
+        rows: winsize.ws_row.into(),
+        columns: winsize.ws_col.into(),
+    })
+}
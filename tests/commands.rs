@@ -7,7 +7,12 @@ use anyhow::{anyhow, Result};
 use assert_cmd::prelude::*;
 use common::{correct_client, correct_server, incorrect_ca_client, incorrect_certificate_client};
 use predicates::prelude::*;
+use std::io::{BufRead, BufReader, Write};
 use std::panic;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 
 #[test]
 #[serial]
@@ -60,6 +65,36 @@ fn run_fails_when_process_doesnt_exist() -> Result<()> {
     }
 }
 
+#[test]
+#[serial]
+fn run_fails_when_disk_rule_names_unknown_device() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let cmd = client.args(vec![
+            "run",
+            "--disk-rule",
+            "/dev/null,rbps=1048576",
+            "--",
+            "true",
+        ]);
+
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("Unknown disk device: /dev/null"));
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
 // mark root-dependent tests as ignored
 // can be executed with cargo test --ignored
 
@@ -137,30 +172,140 @@ fn running_under_constrained_cpu_works() -> Result<()> {
 #[test]
 #[serial]
 #[ignore]
-fn running_under_constrained_memory_makes_cmd_fail_when_going_over() -> Result<()> {
+fn paused_cmd_can_be_resumed_and_stopped() -> Result<()> {
     let mut server = correct_server()?;
     let mut server_child = server.spawn()?;
 
     let result = panic::catch_unwind(move || {
         let mut client = correct_client().unwrap();
 
-        // certainly bash needs more than 10 bytes:
+        let output = client
+            .args(vec!["run", "--cpu", "100", "--", "bash", "-c", "sleep 60"])
+            .output()
+            .unwrap();
+
+        let id = std::str::from_utf8(&output.stdout).unwrap().trim();
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.arg("pause").arg(id);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Paused"));
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.arg("list").arg("--paused");
+
+        cmd.assert().success().stdout(predicate::str::contains(id));
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.arg("resume").arg(id);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Resumed"));
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.arg("status").arg(id);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Running"));
+
+        let mut client = correct_client().unwrap();
+        client.arg("pause").arg(id).assert().success();
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.arg("stop").arg(id);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Stopped"));
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.arg("status").arg(id);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Killed with signal: 15"));
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+#[ignore]
+fn stop_kills_the_whole_tree_of_constrained_cmd() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
 
+        // the daemon escapes the process group but not the control groups
         let output = client
             .args(vec![
-                "run", "--memory", "10", "--", "bash", "-c", "sleep 60",
+                "run",
+                "--cpu",
+                "100",
+                "--",
+                "bash",
+                "-c",
+                "setsid -f sleep 100; sleep 100",
             ])
             .output()
             .unwrap();
 
         let id = std::str::from_utf8(&output.stdout).unwrap().trim();
 
+        thread::sleep(Duration::from_millis(500));
+
         let mut client = correct_client().unwrap();
-        let cmd = client.arg("status").arg(id);
+        let cmd = client.arg("stop").arg(id);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Stopped"));
+
+        // the control groups get deleted once all of their processes are gone
+        thread::sleep(Duration::from_millis(500));
+
+        assert!(!std::path::Path::new("/sys/fs/cgroup/pids")
+            .join(id)
+            .exists());
+        assert!(!std::path::Path::new("/sys/fs/cgroup").join(id).exists());
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+fn clients_listed_in_the_users_policy_get_authorized() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server
+        .args(vec!["--allow-users", "imtotallywrong=nobody"])
+        .spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = incorrect_certificate_client().unwrap();
+
+        let cmd = client.args(vec!["run", "--follow", "--", "echo", "authorized"]);
 
         cmd.assert()
             .success()
-            .stdout(predicate::str::contains("Killed with signal: 9"));
+            .stdout(predicate::str::contains("authorized"));
     });
 
     server_child.kill().unwrap();
@@ -174,7 +319,43 @@ fn running_under_constrained_memory_makes_cmd_fail_when_going_over() -> Result<(
 #[test]
 #[serial]
 #[ignore]
-fn running_under_constrained_disk_works() -> Result<()> {
+fn cmd_runs_as_the_allowed_user_only() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server
+        .args(vec!["--allow-users", "client=nobody"])
+        .spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let cmd = client.args(vec![
+            "run", "--user", "nobody", "--follow", "--", "id", "-un",
+        ]);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("nobody"));
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.args(vec!["run", "--user", "root", "--", "id"]);
+
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("Not allowed"));
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+#[ignore]
+fn running_under_cpu_quota_and_cpuset_works() -> Result<()> {
     let mut server = correct_server()?;
     let mut server_child = server.spawn()?;
 
@@ -182,7 +363,9 @@ fn running_under_constrained_disk_works() -> Result<()> {
         let mut client = correct_client().unwrap();
 
         let output = client
-            .args(vec!["run", "--disk", "100", "--", "bash", "-c", "sleep 60"])
+            .args(vec![
+                "run", "--cpus", "0.5", "--cpuset", "0", "--", "bash", "-c", "sleep 60",
+            ])
             .output()
             .unwrap();
 
@@ -206,22 +389,40 @@ fn running_under_constrained_disk_works() -> Result<()> {
 
 #[test]
 #[serial]
-fn status_returns_running_when_running() -> Result<()> {
+#[ignore]
+fn going_over_the_pids_limit_gets_reported_in_status() -> Result<()> {
     let mut server = correct_server()?;
     let mut server_child = server.spawn()?;
 
     let result = panic::catch_unwind(move || {
         let mut client = correct_client().unwrap();
 
-        let output = client.args(vec!["run", "sleep", "999"]).output().unwrap();
+        let output = client
+            .args(vec![
+                "run",
+                "--pids",
+                "4",
+                "--",
+                "bash",
+                "-c",
+                "for i in $(seq 1 10); do sleep 60 & done; wait",
+            ])
+            .output()
+            .unwrap();
+
         let id = std::str::from_utf8(&output.stdout).unwrap().trim();
 
+        thread::sleep(Duration::from_secs(1));
+
         let mut client = correct_client().unwrap();
         let cmd = client.arg("status").arg(id);
 
         cmd.assert()
             .success()
-            .stdout(predicate::str::contains("Running"));
+            .stdout(predicate::str::contains("Hit the pids limit"));
+
+        let mut client = correct_client().unwrap();
+        client.arg("stop").arg(id).assert().success();
     });
 
     server_child.kill().unwrap();
@@ -234,20 +435,122 @@ fn status_returns_running_when_running() -> Result<()> {
 
 #[test]
 #[serial]
-fn log_streams_the_logs() -> Result<()> {
+#[ignore]
+fn running_under_constrained_memory_makes_cmd_fail_when_going_over() -> Result<()> {
     let mut server = correct_server()?;
     let mut server_child = server.spawn()?;
 
     let result = panic::catch_unwind(move || {
         let mut client = correct_client().unwrap();
 
-        let output = client.args(vec!["run", "seq", "1", "4"]).output().unwrap();
+        // certainly bash needs more than 10 bytes:
+
+        let output = client
+            .args(vec![
+                "run", "--memory", "10", "--", "bash", "-c", "sleep 60",
+            ])
+            .output()
+            .unwrap();
+
         let id = std::str::from_utf8(&output.stdout).unwrap().trim();
 
         let mut client = correct_client().unwrap();
-        let cmd = client.arg("log").arg(id).arg("stdout");
+        let cmd = client.arg("status").arg(id);
 
-        cmd.assert().success().stdout(predicate::str::contains("3"));
+        cmd.assert().success().stdout(predicate::str::contains(
+            "Killed by the OOM killer with signal: 9",
+        ));
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+#[ignore]
+fn peak_memory_of_constrained_cmd_gets_reported_in_status() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let output = client
+            .args(vec![
+                "run",
+                "--memory",
+                "50000000",
+                "--",
+                "bash",
+                "-c",
+                "a=$(head -c 10000000 /dev/zero | tr '\\0' a)",
+            ])
+            .output()
+            .unwrap();
+
+        let id = std::str::from_utf8(&output.stdout).unwrap().trim();
+
+        let mut client = correct_client().unwrap();
+        client.arg("wait").arg(id).assert().success();
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.arg("status").arg(id);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Exited with code: 0"))
+            .stdout(predicate::str::contains("Peak memory usage"));
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+#[ignore]
+fn stats_of_constrained_cmd_are_shown_until_it_finishes() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let output = client
+            .args(vec!["run", "--cpu", "512", "--", "bash", "-c", "sleep 2"])
+            .output()
+            .unwrap();
+
+        let id = std::str::from_utf8(&output.stdout).unwrap().trim();
+
+        let mut client = correct_client().unwrap();
+        let cmd = client
+            .arg("stats")
+            .arg(id)
+            .arg("--watch")
+            .arg("--interval")
+            .arg("500");
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("CPU TIME"));
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.arg("status").arg(id);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Exited with code: 0"))
+            .stdout(predicate::str::contains("CPU time"));
     });
 
     server_child.kill().unwrap();
@@ -258,6 +561,509 @@ fn log_streams_the_logs() -> Result<()> {
     }
 }
 
+#[test]
+#[serial]
+#[ignore]
+fn running_under_constrained_disk_works() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let output = client
+            .args(vec!["run", "--disk", "100", "--", "bash", "-c", "sleep 60"])
+            .output()
+            .unwrap();
+
+        let id = std::str::from_utf8(&output.stdout).unwrap().trim();
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.arg("status").arg(id);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Running"));
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+fn status_returns_running_when_running() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let output = client.args(vec!["run", "sleep", "999"]).output().unwrap();
+        let id = std::str::from_utf8(&output.stdout).unwrap().trim();
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.arg("status").arg(id);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Running"));
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+fn log_streams_the_logs() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let output = client.args(vec!["run", "seq", "1", "4"]).output().unwrap();
+        let id = std::str::from_utf8(&output.stdout).unwrap().trim();
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.arg("log").arg(id).arg("stdout");
+
+        cmd.assert().success().stdout(predicate::str::contains("3"));
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+fn run_with_follow_streams_the_output_and_exits_with_its_code() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let cmd = client.args(vec![
+            "run",
+            "--follow",
+            "--",
+            "bash",
+            "-c",
+            "echo hello; exit 4",
+        ]);
+
+        cmd.assert()
+            .code(4)
+            .stdout(predicate::str::contains("hello"));
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+fn run_with_env_and_cwd_applies_them_to_the_command() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let cmd = client.args(vec![
+            "run",
+            "--env",
+            "GREETING=hello world",
+            "--cwd",
+            "/",
+            "--follow",
+            "--",
+            "bash",
+            "-c",
+            "echo $GREETING; pwd",
+        ]);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("hello world\n/\n"));
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+fn attach_forwards_the_stdin_into_the_command() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let output = client
+            .args(vec!["run", "--open-stdin", "--", "tr", "a-z", "A-Z"])
+            .output()
+            .unwrap();
+
+        let id = std::str::from_utf8(&output.stdout).unwrap().trim();
+
+        let mut client = correct_client().unwrap();
+
+        // the end of the stdin gets forwarded too, letting the command finish
+        client
+            .args(vec!["attach", id])
+            .with_stdin()
+            .buffer("hello\n")
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("HELLO"));
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+fn attach_forwards_the_output_of_a_command_not_reading_the_stdin() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let output = client
+            .args(vec![
+                "run",
+                "--open-stdin",
+                "--",
+                "sh",
+                "-c",
+                "sleep 1; echo done; sleep 5",
+            ])
+            .output()
+            .unwrap();
+
+        let id = std::str::from_utf8(&output.stdout).unwrap().trim();
+
+        let mut client = correct_client().unwrap();
+        let mut attached = client
+            .args(vec!["attach", id])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        // much more than the pipe of the command can hold
+        let mut stdin = attached.stdin.take().unwrap();
+        thread::spawn(move || {
+            let _ = stdin.write_all(&vec![b'x'; 4 << 20]);
+        });
+
+        let stdout = attached.stdout.take().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if sender.send(line.unwrap()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let line = receiver.recv_timeout(Duration::from_secs(4));
+
+        attached.kill().unwrap();
+        attached.wait().unwrap();
+
+        assert!(line.unwrap() == "done");
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+#[test]
+#[serial]
+fn run_with_tty_runs_the_command_in_a_pseudo_terminal() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let output = client
+            .args(vec!["run", "--tty", "--", "sh", "-c", "read _; tty"])
+            .output()
+            .unwrap();
+
+        let id = std::str::from_utf8(&output.stdout).unwrap().trim();
+
+        let mut client = correct_client().unwrap();
+
+        // the stdin of the test isn't a terminal, so it gets forwarded as is
+        client
+            .args(vec!["attach", id])
+            .with_stdin()
+            .buffer("go\n")
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("/dev/pts/"));
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+fn wait_exits_with_the_command_exit_code() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let output = client
+            .args(vec!["run", "--", "bash", "-c", "sleep 1; exit 3"])
+            .output()
+            .unwrap();
+        let id = std::str::from_utf8(&output.stdout).unwrap().trim();
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.arg("wait").arg(id);
+
+        cmd.assert().code(3);
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+fn command_running_for_longer_than_its_timeout_gets_stopped() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let output = client
+            .args(vec!["run", "--timeout", "1", "--", "sleep", "60"])
+            .output()
+            .unwrap();
+        let id = std::str::from_utf8(&output.stdout).unwrap().trim();
+
+        let mut client = correct_client().unwrap();
+
+        // killed with SIGTERM
+        client.arg("wait").arg(id).assert().code(143);
+
+        let mut client = correct_client().unwrap();
+
+        client
+            .arg("status")
+            .arg(id)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(
+                "Timed out, killed with signal: 15",
+            ));
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+fn signal_given_by_name_gets_delivered_to_the_command() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let output = client.args(vec!["run", "sleep", "60"]).output().unwrap();
+        let id = std::str::from_utf8(&output.stdout).unwrap().trim();
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.arg("signal").arg(id).arg("usr1");
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Sent"));
+
+        // killed by SIGUSR1 (10)
+        let mut client = correct_client().unwrap();
+        let cmd = client.arg("wait").arg(id);
+
+        cmd.assert().code(128 + 10);
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+fn delete_forgets_finished_commands() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let output = client
+            .args(vec!["run", "--follow", "--", "echo", "hello"])
+            .output()
+            .unwrap();
+        // the id goes to the stderr when following
+        let id = std::str::from_utf8(&output.stderr)
+            .unwrap()
+            .trim()
+            .to_string();
+
+        let mut client = correct_client().unwrap();
+        client.args(vec!["delete", &id]).assert().success();
+
+        let mut client = correct_client().unwrap();
+        client
+            .args(vec!["status", &id])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Process not found"));
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+fn log_with_tail_and_no_follow_prints_the_last_lines() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let output = client
+            .args(vec!["run", "--", "bash", "-c", "seq 1 5; sleep 999"])
+            .output()
+            .unwrap();
+        let id = std::str::from_utf8(&output.stdout).unwrap().trim();
+
+        std::thread::sleep(std::time::Duration::from_millis(500));
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.args(vec!["log", id, "stdout", "--tail", "2", "--no-follow"]);
+
+        cmd.assert().success().stdout("4\n5\n");
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+#[serial]
+fn list_shows_started_commands() -> Result<()> {
+    let mut server = correct_server()?;
+    let mut server_child = server.spawn()?;
+
+    let result = panic::catch_unwind(move || {
+        let mut client = correct_client().unwrap();
+
+        let output = client.args(vec!["run", "sleep", "999"]).output().unwrap();
+        let id = std::str::from_utf8(&output.stdout).unwrap().trim();
+
+        let mut client = correct_client().unwrap();
+        let cmd = client.args(vec!["list", "--running", "--command", "sleep"]);
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains(id))
+            .stdout(predicate::str::contains("sleep 999"));
+    });
+
+    server_child.kill().unwrap();
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow!("panic occurred")),
+    }
+}
+
+#[test]
+fn server_refuses_to_start_with_zero_retention_interval() -> Result<()> {
+    let mut server = correct_server()?;
+
+    server
+        .args(vec!["--retain-jobs", "10", "--retention-interval", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the interval has to be at least 1 second",
+        ));
+
+    Ok(())
+}
+
 #[test]
 #[serial]
 fn pointing_at_invalid_ca_makes_client_fail() -> Result<()> {